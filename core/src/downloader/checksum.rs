@@ -0,0 +1,175 @@
+use crate::downloader::report::DownloadError;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Sha1 => write!(f, "sha1"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+            HashAlgorithm::Sha512 => write!(f, "sha512"),
+        }
+    }
+}
+
+// +============================+
+//           Checksums
+// +============================+
+
+/// Expected integrity of a file: any mix of hashes plus its size
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checksums {
+    size: Option<u64>,
+    hashes: Vec<(HashAlgorithm, String)>,
+}
+
+impl Checksums {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Expected hex digest for `algorithm`, replaces a previous one of the same algorithm
+    pub fn hash(mut self, algorithm: HashAlgorithm, hex: impl Into<String>) -> Self {
+        self.hashes.retain(|(a, _)| *a != algorithm);
+        self.hashes.push((algorithm, hex.into().to_lowercase()));
+        self
+    }
+
+    pub fn sha1(self, hex: impl Into<String>) -> Self {
+        self.hash(HashAlgorithm::Sha1, hex)
+    }
+
+    pub fn sha256(self, hex: impl Into<String>) -> Self {
+        self.hash(HashAlgorithm::Sha256, hex)
+    }
+
+    pub fn sha512(self, hex: impl Into<String>) -> Self {
+        self.hash(HashAlgorithm::Sha512, hex)
+    }
+
+    pub fn expected_size(&self) -> Option<u64> {
+        self.size
+    }
+
+    pub fn get(&self, algorithm: HashAlgorithm) -> Option<&String> {
+        self.hashes.iter().find(|(a, _)| *a == algorithm).map(|(_, h)| h)
+    }
+
+    pub fn hashes(&self) -> &Vec<(HashAlgorithm, String)> {
+        &self.hashes
+    }
+
+    /// No hash to check, the content of the file can't be trusted
+    pub fn has_hashes(&self) -> bool {
+        !self.hashes.is_empty()
+    }
+
+    /// Rejects a wrong size without reading the file
+    pub fn verify_size(&self, size: u64) -> Result<(), DownloadError> {
+        match self.size {
+            Some(expected) if expected != size => Err(DownloadError::SizeMismatch { expected, found: size }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks a file on disk, the size first and the hashes in a single read
+    pub fn verify_file(&self, path: &Path) -> Result<(), DownloadError> {
+        self.verify_size(fs::metadata(path)?.len())?;
+        let mut hasher = self.hasher();
+        hasher.update_from(path)?;
+        hasher.verify(self)
+    }
+
+    /// Hasher for the algorithms this expects
+    pub fn hasher(&self) -> StreamingHasher {
+        let has = |a| self.get(a).is_some();
+        StreamingHasher {
+            size: 0,
+            sha1: has(HashAlgorithm::Sha1).then(Sha1::new),
+            sha256: has(HashAlgorithm::Sha256).then(Sha256::new),
+            sha512: has(HashAlgorithm::Sha512).then(Sha512::new),
+        }
+    }
+}
+
+// +============================+
+//        StreamingHasher
+// +============================+
+
+/// Computes the hashes of a file as its bytes go by, so it doesn't need a second read
+pub struct StreamingHasher {
+    size: u64,
+    sha1: Option<Sha1>,
+    sha256: Option<Sha256>,
+    sha512: Option<Sha512>,
+}
+
+impl StreamingHasher {
+    pub fn update(&mut self, data: &[u8]) {
+        self.size += data.len() as u64;
+        if let Some(h) = &mut self.sha1 {
+            h.update(data);
+        }
+        if let Some(h) = &mut self.sha256 {
+            h.update(data);
+        }
+        if let Some(h) = &mut self.sha512 {
+            h.update(data);
+        }
+    }
+
+    /// Feeds the content of a file, e.g. the part already on disk of a resumed download
+    pub fn update_from(&mut self, path: &Path) -> io::Result<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut buffer = [0; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.update(&buffer[..read]);
+        }
+    }
+
+    /// Bytes hashed so far
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn verify(self, expected: &Checksums) -> Result<(), DownloadError> {
+        expected.verify_size(self.size)?;
+        let found = [
+            (HashAlgorithm::Sha1, self.sha1.map(|h| hex::encode(h.finalize()))),
+            (HashAlgorithm::Sha256, self.sha256.map(|h| hex::encode(h.finalize()))),
+            (HashAlgorithm::Sha512, self.sha512.map(|h| hex::encode(h.finalize()))),
+        ];
+        for (algorithm, found) in found {
+            if let (Some(expected), Some(found)) = (expected.get(algorithm), found)
+                && *expected != found
+            {
+                return Err(DownloadError::ChecksumMismatch {
+                    algorithm,
+                    expected: expected.clone(),
+                    found,
+                });
+            }
+        }
+        Ok(())
+    }
+}
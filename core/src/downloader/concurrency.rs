@@ -0,0 +1,277 @@
+use crate::downloader::downloader::DownloadPriority;
+use crate::downloader::report::DownloadError;
+use reqwest::Url;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Connections an adaptive limit starts with
+const ADAPTIVE_START: usize = 8;
+/// Minimum time between two cuts, the errors of one congestion come in bursts
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(2);
+/// Window the throughput is compared over before raising the limit
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(1);
+/// A response slower than this many times the average latency counts as congestion
+const LATENCY_SPIKE: u32 = 4;
+/// Longest `Retry-After` honoured, a bigger one is most likely a misconfigured server
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+// +============================+
+//      ConcurrencyControl
+// +============================+
+
+/// Connections the downloads of a `Downloader` may open at once, in total and per host.
+/// A free connection goes to the waiting download of best `DownloadPriority`.
+///
+/// In adaptive mode the total limit grows by one per round of successful downloads while the
+/// throughput keeps improving and is halved on throttling, timeouts and latency spikes (AIMD).
+#[derive(Debug)]
+pub struct ConcurrencyControl {
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: f64,
+    max: usize,
+    adaptive: bool,
+    per_host: Option<usize>,
+    active: usize,
+    hosts: HashMap<String, Host>,
+    /// Downloads waiting for a connection, by priority and host
+    waiting: BTreeMap<DownloadPriority, HashMap<String, usize>>,
+    latency: Option<Duration>,
+    last_decrease: Option<Instant>,
+    window_start: Instant,
+    window_bytes: u64,
+    last_rate: f64,
+    improving: bool,
+}
+
+#[derive(Debug, Default)]
+struct Host {
+    active: usize,
+    /// Set by a `Retry-After`, no connection to the host is opened before it
+    not_before: Option<Instant>,
+}
+
+impl ConcurrencyControl {
+    /// `max` connections at once, the limit only moves below it in adaptive mode
+    pub fn new(max: usize, adaptive: bool, per_host: Option<usize>) -> Self {
+        let max = max.max(1);
+        let limit = if adaptive { ADAPTIVE_START.min(max) } else { max };
+        Self {
+            state: Mutex::new(State {
+                limit: limit as f64,
+                max,
+                adaptive,
+                per_host: per_host.map(|n| n.max(1)),
+                active: 0,
+                hosts: HashMap::new(),
+                waiting: BTreeMap::new(),
+                latency: None,
+                last_decrease: None,
+                window_start: Instant::now(),
+                window_bytes: 0,
+                last_rate: 0.0,
+                improving: true,
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Current limit of connections
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit as usize
+    }
+
+    /// Waits for a free connection to the host of `url`, it is given back when the slot drops
+    pub async fn acquire(self: &Arc<Self>, url: &str, priority: DownloadPriority) -> Slot {
+        let host = host_of(url);
+        let mut waiting: Option<Waiting> = None;
+        loop {
+            let notified = self.notify.notified();
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                match state.try_acquire(&host, priority) {
+                    Ok(()) => {
+                        drop(state);
+                        drop(waiting);
+                        return Slot { control: self.clone(), host };
+                    }
+                    Err(wait) => wait,
+                }
+            };
+            if waiting.is_none() {
+                waiting = Some(Waiting::new(self.clone(), priority, &host));
+            }
+            match wait {
+                Some(wait) => {
+                    tokio::select! {
+                        _ = notified => {}
+                        _ = tokio::time::sleep(wait) => {}
+                    }
+                }
+                None => notified.await,
+            }
+        }
+    }
+
+    /// A download finished, `latency` is the time the source took to answer
+    pub fn on_success(&self, latency: Duration, bytes: u64) {
+        let mut state = self.state.lock().unwrap();
+        if !state.adaptive {
+            return;
+        }
+        let average = *state.latency.get_or_insert(latency);
+        state.latency = Some((average * 7 + latency) / 8);
+        if latency > average * LATENCY_SPIKE && latency > Duration::from_millis(500) {
+            log::debug!("Latency spike: {:?}, average {:?}", latency, average);
+            state.decrease();
+        } else {
+            state.increase(bytes);
+        }
+        drop(state);
+        self.notify.notify_waiters();
+    }
+
+    /// A download attempt failed, congestion cuts the limit and `Retry-After` pauses the host
+    pub fn on_error(&self, url: &str, error: &DownloadError) {
+        let mut state = self.state.lock().unwrap();
+        if let DownloadError::Throttled { retry_after: Some(retry_after) } = error {
+            let until = Instant::now() + (*retry_after).min(MAX_RETRY_AFTER);
+            log::info!("{} asked to wait {:?}", host_of(url), retry_after);
+            let host = state.hosts.entry(host_of(url)).or_default();
+            host.not_before = Some(host.not_before.map_or(until, |t| t.max(until)));
+        }
+        if state.adaptive && error.is_congestion() {
+            state.decrease();
+        }
+    }
+}
+
+impl State {
+    /// Takes a connection, or tells how long the host asked to wait, if it did
+    fn try_acquire(&mut self, host: &str, priority: DownloadPriority) -> Result<(), Option<Duration>> {
+        let now = Instant::now();
+        // a better download held back by its own host, paused or at its cap, doesn't get
+        // the connection anyway, it only goes first when it waits on the same host or the total
+        let held_back = self
+            .waiting
+            .range(..priority)
+            .flat_map(|(_, hosts)| hosts.iter())
+            .any(|(waiting, n)| *n > 0 && (waiting == host || self.host_available(waiting, now)));
+        if held_back {
+            return Err(None);
+        }
+        let per_host = self.per_host;
+        let over_limit = self.active >= (self.limit as usize).max(1);
+        let entry = self.hosts.entry(host.to_string()).or_default();
+        if let Some(not_before) = entry.not_before {
+            if not_before > now {
+                return Err(Some(not_before - now));
+            }
+            entry.not_before = None;
+        }
+        if over_limit || per_host.is_some_and(|cap| entry.active >= cap) {
+            return Err(None);
+        }
+        entry.active += 1;
+        self.active += 1;
+        Ok(())
+    }
+
+    /// The host would take one more connection if the total limit allowed it
+    fn host_available(&self, host: &str, now: Instant) -> bool {
+        self.hosts.get(host).is_none_or(|entry| {
+            entry.not_before.is_none_or(|t| t <= now) && self.per_host.is_none_or(|cap| entry.active < cap)
+        })
+    }
+
+    fn release(&mut self, host: &str) {
+        self.active -= 1;
+        if let Some(entry) = self.hosts.get_mut(host) {
+            entry.active -= 1;
+        }
+    }
+
+    fn increase(&mut self, bytes: u64) {
+        self.window_bytes += bytes;
+        let elapsed = self.window_start.elapsed();
+        if elapsed >= THROUGHPUT_WINDOW {
+            let rate = self.window_bytes as f64 / elapsed.as_secs_f64();
+            self.improving = rate >= self.last_rate;
+            self.last_rate = rate;
+            self.window_start = Instant::now();
+            self.window_bytes = 0;
+        }
+        if self.improving {
+            // one more connection per round of `limit` successes
+            self.limit = (self.limit + 1.0 / self.limit).min(self.max as f64);
+        }
+    }
+
+    fn decrease(&mut self) {
+        if self.last_decrease.is_some_and(|t| t.elapsed() < DECREASE_COOLDOWN) {
+            return;
+        }
+        self.limit = (self.limit / 2.0).max(1.0);
+        self.last_decrease = Some(Instant::now());
+        log::debug!("Concurrency limit cut to {}", self.limit as usize);
+    }
+}
+
+fn host_of(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .unwrap_or_default()
+}
+
+// +============================+
+//             Slot
+// +============================+
+
+/// A connection taken from a `ConcurrencyControl`
+#[derive(Debug)]
+pub struct Slot {
+    control: Arc<ConcurrencyControl>,
+    host: String,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.control.state.lock().unwrap().release(&self.host);
+        self.control.notify.notify_waiters();
+    }
+}
+
+/// Registers a download waiting for a connection until it gets one or gives up
+struct Waiting {
+    control: Arc<ConcurrencyControl>,
+    priority: DownloadPriority,
+    host: String,
+}
+
+impl Waiting {
+    fn new(control: Arc<ConcurrencyControl>, priority: DownloadPriority, host: &str) -> Self {
+        let mut state = control.state.lock().unwrap();
+        *state.waiting.entry(priority).or_default().entry(host.to_string()).or_default() += 1;
+        drop(state);
+        Self { control, priority, host: host.to_string() }
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        let mut state = self.control.state.lock().unwrap();
+        if let Some(n) = state.waiting.get_mut(&self.priority).and_then(|hosts| hosts.get_mut(&self.host)) {
+            *n -= 1;
+        }
+        drop(state);
+        // the downloads it held back may go now
+        self.control.notify.notify_waiters();
+    }
+}
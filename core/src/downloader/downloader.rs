@@ -1,19 +1,28 @@
-use crate::tasks::tasks::{ConcurrentTask, Task, TaskResult};
-use crate::versions::verifier::VersionVerifier;
+use crate::downloader::checksum::{Checksums, HashAlgorithm};
+use crate::downloader::concurrency::ConcurrencyControl;
+use crate::downloader::events::{emit, DownloadEvent, EventSender};
+use crate::downloader::report::{DownloadError, DownloadReport, FileOutcome, FileReport};
+use crate::tasks::progress::Progress;
+use crate::tasks::retry::{Retry, RetryPolicy};
+use crate::tasks::tasks::{CancellationToken, ConcurrentTask, Task, TaskResult};
 use futures_util::StreamExt;
+use crate::downloader::limiter::BandwidthLimiter;
+use crate::downloader::mirrors::Mirrors;
+use crate::downloader::registry::{self, Claim};
+use crate::downloader::transport::{DefaultTransport, Transport};
 use reqwest::Client;
-use std::collections::HashMap;
-use std::error::Error;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::io;
-use std::path::Path;
-use std::pin::Pin;
-use std::sync::{Arc, RwLock};
-use std::time::Duration;
-use futures_util::future::join_all;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
-use tokio::{fs::File as AsyncFile, task};
+use tokio::{fs::File as AsyncFile, fs::OpenOptions as AsyncOpenOptions};
+
+/// Suffix of the files being written while a download is in progress
+pub const PART_EXTENSION: &str = ".part";
 
 // +============================+
 //       DonwloaderTracking
@@ -23,16 +32,19 @@ use tokio::{fs::File as AsyncFile, task};
 pub struct DownloaderTracking {
     state: DownloadState,
     progress: (usize, usize),
-    units: Vec<Arc<RwLock<FileProgress>>>,
+    units: HashMap<String, FileProgress>,
+    phases: HashMap<DownloadPhase, PhaseProgress>,
+    throughput: Throughput,
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub enum DownloadState {
     #[default]
     NotDownloading,
     DownloadingInitials,
     Downloading,
     Finished,
+    Aborted,
 }
 
 impl DownloaderTracking {
@@ -40,7 +52,9 @@ impl DownloaderTracking {
         Self {
             state: Default::default(),
             progress,
-            units: Vec::new(),
+            units: HashMap::new(),
+            phases: HashMap::new(),
+            throughput: Throughput::default(),
         }
     }
 
@@ -84,55 +98,213 @@ impl DownloaderTracking {
         self.progress.0 >= self.progress.1
     }
 
-    pub fn units(&self) -> Vec<Arc<RwLock<FileProgress>>> {
-        self.units.clone()
+    pub fn units(&self) -> Vec<&FileProgress> {
+        self.units.values().collect()
     }
 
-    pub fn add_unit(&mut self, unit: Arc<RwLock<FileProgress>>) {
-        self.units.push(unit)
+    pub fn add_unit(&mut self, unit: FileProgress) {
+        self.units.insert(unit.name.clone(), unit);
     }
 
-    pub async fn remove_unit(&mut self, unit: String) {
-        // Filtra la colección de manera asíncrona
-        let filtered_units: Vec<_> = join_all(self.units.iter().map(|u| {
-            let unit_name = unit.to_string();
-            let u_clone = Arc::clone(u);
-            async move {
-                let guard = u_clone.read().unwrap();
-                if !guard.name.eq(&unit_name) {
-                    Some(u_clone.clone())
-                } else {
-                    None
-                }
-            }
-        }))
-        .await
-        .into_iter()
-        .flatten()
-        .collect();
+    pub fn remove_unit(&mut self, unit: &str) -> Option<FileProgress> {
+        self.units.remove(unit)
+    }
 
-        // Reemplaza la colección original
-        self.units = filtered_units;
+    /// Bytes downloaded and expected across every phase
+    pub fn bytes(&self) -> (u64, u64) {
+        self.phases
+            .values()
+            .fold((0, 0), |(done, total), p| (done + p.bytes.0, total + p.bytes.1))
+    }
+
+    /// Rolling transfer rate in bytes per second
+    pub fn rate(&self) -> f64 {
+        self.throughput.rate()
+    }
+
+    pub fn eta(&self) -> Option<Duration> {
+        let (done, total) = self.bytes();
+        self.throughput.eta(total.saturating_sub(done))
+    }
+
+    pub fn phase(&self, phase: DownloadPhase) -> Option<&PhaseProgress> {
+        self.phases.get(&phase)
     }
 
     pub fn clean(&mut self) {
         self.state = DownloadState::NotDownloading;
         self.progress = (0, 0);
         self.units.clear();
+        self.phases.clear();
+        self.throughput = Throughput::default();
+    }
+
+    /// Counts `bytes` as done, only the ones `transferred` in this run count toward the rate.
+    /// Skipped files and resumed parts would make the rate spike otherwise.
+    fn add_bytes(&mut self, phase: DownloadPhase, bytes: u64, transferred: bool) {
+        let now = Instant::now();
+        let p = self.phases.entry(phase).or_default();
+        p.bytes.0 += bytes;
+        if transferred {
+            p.throughput.record(now, bytes);
+            self.throughput.record(now, bytes);
+        }
+    }
+
+    /// Folds an event published by the downloader into the tracking state.
+    pub fn apply(&mut self, event: &DownloadEvent) {
+        match event {
+            DownloadEvent::PhaseChanged(state) => {
+                // a new install starts from scratch, later states keep the totals
+                if matches!(state, DownloadState::NotDownloading | DownloadState::DownloadingInitials) {
+                    self.clean();
+                }
+                self.state = *state;
+            }
+            DownloadEvent::Queued { phase, files, bytes } => {
+                self.progress.1 += files;
+                let p = self.phases.entry(*phase).or_default();
+                p.files.1 += files;
+                p.bytes.1 += bytes;
+            }
+            DownloadEvent::FileStarted { path, phase, downloaded, total, expected } => {
+                let p = self.phases.entry(*phase).or_default();
+                if expected.is_none() && !self.units.contains_key(path) {
+                    // no size in the metadata, the response tells how big it is
+                    p.bytes.1 += total;
+                }
+                // a resumed or retried file restarts from `downloaded`
+                let previous = self.units.get(path).map(|u| u.actual_progress() as u64).unwrap_or(0);
+                if *downloaded > previous {
+                    self.add_bytes(*phase, downloaded - previous, false);
+                }
+                let retries = self.units.get(path).map(|u| u.retries()).unwrap_or(0);
+                let mut unit = FileProgress::new(path.clone());
+                unit.set_phase(*phase);
+                unit.set_retries(retries);
+                unit.set_progress((previous.max(*downloaded) as usize, *total as usize));
+                self.add_unit(unit);
+            }
+            DownloadEvent::FileBytes { path, downloaded, total } => {
+                let Some(unit) = self.units.get_mut(path) else { return };
+                let previous = unit.actual_progress() as u64;
+                let phase = unit.phase();
+                unit.set_stalled(false);
+                if *downloaded > previous {
+                    unit.set_progress((*downloaded as usize, *total as usize));
+                    self.add_bytes(phase, downloaded - previous, true);
+                }
+            }
+            DownloadEvent::FileStalled { path, .. } => {
+                if let Some(unit) = self.units.get_mut(path) {
+                    unit.set_stalled(true);
+                }
+            }
+            DownloadEvent::FileRetrying { path, phase, attempt, .. } => {
+                // a file that failed before its first byte has no unit yet
+                let unit = self.units.entry(path.clone()).or_insert_with(|| {
+                    let mut unit = FileProgress::new(path.clone());
+                    unit.set_phase(*phase);
+                    unit
+                });
+                unit.set_retries(*attempt);
+            }
+            DownloadEvent::FileFinished { path, phase, size } => {
+                let counted = self.remove_unit(path).map(|u| u.actual_progress() as u64).unwrap_or(0);
+                self.add_bytes(*phase, size.saturating_sub(counted), false);
+                self.phases.entry(*phase).or_default().files.0 += 1;
+                self.progress.0 += 1;
+            }
+            DownloadEvent::FileFailed { path, phase, .. } => {
+                self.remove_unit(path);
+                self.phases.entry(*phase).or_default().files.0 += 1;
+                self.progress.0 += 1;
+            }
+        }
+    }
+}
+
+/// Files and bytes of a single phase of an install
+#[derive(Debug, Default)]
+pub struct PhaseProgress {
+    files: (usize, usize),
+    bytes: (u64, u64),
+    throughput: Throughput,
+}
+
+impl PhaseProgress {
+    pub fn files(&self) -> (usize, usize) {
+        self.files
+    }
+
+    pub fn bytes(&self) -> (u64, u64) {
+        self.bytes
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.throughput.rate()
+    }
+
+    pub fn eta(&self) -> Option<Duration> {
+        self.throughput.eta(self.bytes.1.saturating_sub(self.bytes.0))
+    }
+}
+
+/// Transfer rate over the last `THROUGHPUT_WINDOW`
+#[derive(Debug, Default)]
+struct Throughput {
+    /// Bytes transferred so far
+    transferred: u64,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+
+impl Throughput {
+    fn record(&mut self, at: Instant, bytes: u64) {
+        self.transferred += bytes;
+        self.samples.push_back((at, self.transferred));
+        // keep one sample older than the window as the baseline
+        while self.samples.len() > 2 && at.duration_since(self.samples[1].0) > THROUGHPUT_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    fn rate(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some((start, from)), Some((end, to))) if end > start => {
+                (to - from) as f64 / end.duration_since(*start).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn eta(&self, remaining: u64) -> Option<Duration> {
+        let rate = self.rate();
+        if rate <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f64(remaining as f64 / rate))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct FileProgress {
     name: String,
+    phase: DownloadPhase,
     progress: (usize, usize),
+    stalled: bool,
+    retries: u32,
 }
 
 impl FileProgress {
     pub fn new(name: String) -> Self {
         Self {
             name,
+            phase: DownloadPhase::default(),
             progress: (0, 0),
+            stalled: false,
+            retries: 0,
         }
     }
 }
@@ -146,6 +318,32 @@ impl FileProgress {
         self.name = name;
     }
 
+    pub fn phase(&self) -> DownloadPhase {
+        self.phase
+    }
+
+    pub fn set_phase(&mut self, phase: DownloadPhase) {
+        self.phase = phase;
+    }
+
+    /// No bytes arrived within the stall timeout, the download is waiting for a retry
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
+    }
+
+    pub fn set_stalled(&mut self, stalled: bool) {
+        self.stalled = stalled;
+    }
+
+    /// Failed attempts so far, the download is on attempt `retries + 1`
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+
     pub fn progress(&self) -> (usize, usize) {
         self.progress
     }
@@ -170,16 +368,143 @@ impl FileProgress {
     }
 }
 
+/// Kind of file being downloaded, progress is broken down by it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DownloadPhase {
+    /// Version json, asset index and client jar
+    #[default]
+    Initials,
+    Libraries,
+    Assets,
+}
+
+/// Order in which queued files are downloaded, lowest first, so an interrupted
+/// install still has what the game needs to start
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DownloadPriority {
+    Initials,
+    Libraries,
+    Natives,
+    Assets,
+    Sounds,
+}
+
+impl DownloadPriority {
+    /// The game starts without the files of this priority
+    pub fn is_optional(&self) -> bool {
+        *self >= DownloadPriority::Assets
+    }
+}
+
+impl From<DownloadPhase> for DownloadPriority {
+    fn from(phase: DownloadPhase) -> Self {
+        match phase {
+            DownloadPhase::Initials => DownloadPriority::Initials,
+            DownloadPhase::Libraries => DownloadPriority::Libraries,
+            DownloadPhase::Assets => DownloadPriority::Assets,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FileData {
     path: String,
     url: String,
-    sha1: Option<String>,
+    fallbacks: Vec<String>,
+    checksums: Checksums,
+    phase: DownloadPhase,
+    priority: Option<DownloadPriority>,
 }
 
 impl FileData {
     pub fn new(path: String, url: String, sha1: Option<String>) -> Self {
-        Self { path, url, sha1 }
+        let checksums = match sha1 {
+            Some(sha1) => Checksums::new().sha1(sha1),
+            None => Checksums::new(),
+        };
+        Self { path, url, fallbacks: Vec::new(), checksums, phase: DownloadPhase::default(), priority: None }
+    }
+
+    /// Url tried when the previous ones fail or serve a corrupt file
+    pub fn fallback(mut self, url: String) -> Self {
+        self.fallbacks.push(url);
+        self
+    }
+
+    /// Expected size in bytes, as published in the version or asset json
+    pub fn size(mut self, size: u64) -> Self {
+        self.checksums = self.checksums.size(size);
+        self
+    }
+
+    /// Adds an expected hash, e.g. the sha256 of a java runtime or the sha512 of a mod
+    pub fn checksum(mut self, algorithm: HashAlgorithm, hex: impl Into<String>) -> Self {
+        self.checksums = self.checksums.hash(algorithm, hex);
+        self
+    }
+
+    pub fn phase(mut self, phase: DownloadPhase) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    /// Overrides the priority given by the phase
+    pub fn priority(mut self, priority: DownloadPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Every url of the file in the order they are tried
+    pub fn urls(&self) -> Vec<&str> {
+        std::iter::once(self.url.as_str())
+            .chain(self.fallbacks.iter().map(|u| u.as_str()))
+            .collect()
+    }
+
+    /// Puts the configured mirrors of each url before it
+    pub fn with_mirrors(mut self, mirrors: &Mirrors) -> Self {
+        if mirrors.is_empty() {
+            return self;
+        }
+        let mut urls: Vec<String> = Vec::new();
+        for url in self.urls() {
+            for candidate in mirrors.candidates(url) {
+                if !urls.contains(&candidate) {
+                    urls.push(candidate);
+                }
+            }
+        }
+        self.url = urls.remove(0);
+        self.fallbacks = urls;
+        self
+    }
+
+    pub fn sha1(&self) -> Option<&String> {
+        self.checksums.get(HashAlgorithm::Sha1)
+    }
+
+    pub fn checksums(&self) -> &Checksums {
+        &self.checksums
+    }
+
+    pub fn expected_size(&self) -> Option<u64> {
+        self.checksums.expected_size()
+    }
+
+    pub fn download_phase(&self) -> DownloadPhase {
+        self.phase
+    }
+
+    pub fn download_priority(&self) -> DownloadPriority {
+        self.priority.unwrap_or_else(|| self.phase.into())
     }
 }
 
@@ -188,10 +513,21 @@ impl FileData {
 // +============================+
 
 pub struct Downloader {
-    client: Client,
+    context: DownloadContext,
     concurrent_downloads: usize,
-    retries: u16,
-    progress: Option<Arc<Mutex<DownloaderTracking>>>,
+    mirrors: Mirrors,
+}
+
+/// What a single file download shares with the rest of its `Downloader`
+#[derive(Debug, Clone)]
+pub struct DownloadContext {
+    transport: Arc<dyn Transport>,
+    retry: RetryPolicy,
+    events: Option<EventSender>,
+    cancel: CancellationToken,
+    limiter: BandwidthLimiter,
+    concurrency: Arc<ConcurrencyControl>,
+    stall_timeout: Duration,
 }
 
 unsafe impl Send for Downloader {}
@@ -201,163 +537,398 @@ impl Downloader {
         Builder::default()
     }
 
-    pub async fn download_files_concurrently(&self, files: Vec<FileData>) -> io::Result<()> {
+    /// Downloads the files in order of priority, the files the game needs before the assets
+    pub async fn download_files_concurrently(&self, files: Vec<FileData>) -> io::Result<DownloadReport> {
+        self.download_files_with_progress(files, &Progress::new("download")).await
+    }
+
+    /// Same as `download_files_concurrently`, each file is a child of `progress` weighted by its size
+    pub async fn download_files_with_progress(&self, mut files: Vec<FileData>, progress: &Progress) -> io::Result<DownloadReport> {
+        files.sort_by_key(|f| f.download_priority());
+        let report = Arc::new(Mutex::new(DownloadReport::new()));
         let tasks = files
             .iter()
             .map(|f| DownloadTask {
-                client: self.client.clone(),
-                file: f.clone(),
-                file_progress: None,
-                global_progess: self.progress.clone(),
+                file: f.clone().with_mirrors(&self.mirrors),
+                context: DownloadContext {
+                    cancel: self.context.cancel.child_token(),
+                    ..self.context.clone()
+                },
+                report: report.clone(),
             })
             .collect();
-        ConcurrentTask::new(tasks, self.concurrent_downloads)
+        let mut queued: HashMap<DownloadPhase, (usize, u64)> = HashMap::new();
+        for f in &files {
+            let q = queued.entry(f.phase).or_default();
+            q.0 += 1;
+            q.1 += f.expected_size().unwrap_or(0);
+        }
+        for (phase, (files, bytes)) in queued {
+            emit(self.context.events.as_ref(), DownloadEvent::Queued { phase, files, bytes });
+        }
+        let results = ConcurrentTask::new(tasks, self.concurrent_downloads)
+            .cancellation(self.context.cancel.clone())
+            .progress(progress.clone())
             .run()
             .await;
-        
-        /*
-        log::info!("Downloading files");
-        let semaphore = Arc::new(Semaphore::new(self.concurrent_downloads));
-
-        let tasks: Vec<_> = files
-            .into_iter()
-            .map(|v| {
-                let client = self.client.clone();
-                let permit = semaphore.clone().acquire_owned();
-                let value = v.clone();
-                let progress = self.progress.clone();
-                task::spawn(async move {
-                    let _permit = permit.await;
-
-                    // add to vector
-                    let url = value.url.clone();
-                    let mut file_progress: Option<Arc<RwLock<FileProgress>>> = None;
-                    if let Some(p) = progress.clone() {
-                        let fp = Arc::new(RwLock::new(FileProgress::new(url.clone())));
-                        p.lock().await.add_unit(fp.clone());
-                        file_progress = Some(fp);
-                    }
 
-                    let res = Self::download_file(value, client, file_progress.clone()).await;
+        let mut report = std::mem::take(&mut *report.lock().await);
+        // a task that died before reporting its file still failed it
+        let reported: HashSet<String> = report.files().iter().map(|f| f.file().path.clone()).collect();
+        for (index, error) in results.failures() {
+            if !reported.contains(&files[index].path) {
+                let error = DownloadError::Io(io::Error::other(error.clone()));
+                report.push(FileReport::new(files[index].clone(), FileOutcome::Failed(error), 0, Duration::ZERO));
+            }
+        }
+        if self.context.cancel.is_cancelled() {
+            // list the files that never started too, the report has to tell what is missing
+            let reported: HashSet<String> = report.files().iter().map(|f| f.file().path.clone()).collect();
+            for file in files.into_iter().filter(|f| !reported.contains(&f.path)) {
+                report.push(FileReport::new(file, FileOutcome::Aborted, 0, Duration::ZERO));
+            }
+            report.set_aborted();
+        }
+        if report.is_success() {
+            log::info!("Download finished: {}", report);
+        } else {
+            log::error!("Download finished with errors: {}", report);
+        }
+        Ok(report)
+    }
 
-                    // add 1 to actual progress and file download from remove to vector
-                    if let Some(p) = progress {
-                        let mut pro = p.lock().await;
-                        let actual_progress = pro.actual_progress();
-                        pro.set_actual_progress(actual_progress + 1);
-                        pro.remove_unit(url).await;
-                    }
-                    drop(_permit);
-                    res
-                })
-            })
-            .collect();
+    pub async fn download_file(file: &FileData, context: &DownloadContext) -> FileReport {
+        let events = context.events.as_ref();
+        let start = Instant::now();
 
-        let results = join_all(tasks).await;
-        for result in results {
-            match result {
-                Ok(Ok(())) => {} // La tarea se completó correctamente
-                Ok(Err(e)) => log::error!("Error descargando el archivo: {:?}", e),
-                Err(e) => log::error!("Error en la tarea: {:?}", e),
+        // another install fetching the same file finishes it first, then it's usually valid here
+        let _claim = loop {
+            match registry::claim(Path::new(&file.path)) {
+                Claim::Owner(guard) => break guard,
+                Claim::Busy(wait) => {
+                    log::debug!("{} is already being downloaded, waiting for it", &file.path);
+                    tokio::select! {
+                        _ = wait.finished() => {}
+                        _ = context.cancel.cancelled() => {
+                            emit(events, DownloadEvent::FileFailed {
+                                path: file.path.clone(),
+                                phase: file.phase,
+                                error: DownloadError::Aborted.to_string(),
+                            });
+                            return FileReport::new(file.clone(), FileOutcome::Aborted, 0, start.elapsed());
+                        }
+                    }
+                }
             }
+        };
+        if Self::verify_file(&file) {
+            let _ = fs::remove_file(Self::part_path(Path::new(&file.path)));
+            emit(events, DownloadEvent::FileFinished {
+                path: file.path.clone(),
+                phase: file.phase,
+                size: file.expected_size().unwrap_or(0),
+            });
+            return FileReport::new(file.clone(), FileOutcome::Skipped, 0, start.elapsed());
         }
-        if let Some(p) = self.progress.clone() {
-            let mut p = p.lock().await;
-            p.state = DownloadState::Finished;
+
+        // every failure moves on to the next url, so each mirror gets at least one attempt
+        let policy = context.retry.max_attempts(context.retry.attempts().max(file.urls().len() as u32));
+        let (path, phase, sender) = (file.path.clone(), file.phase, context.events.clone());
+        let mut task = Retry::new(DownloadAttempt::new(file, context), policy)
+            .retry_if(|attempt, _| attempt.is_retryable())
+            .cancellation(context.cancel.clone())
+            .on_retry(move |retry| {
+                emit(sender.as_ref(), DownloadEvent::FileRetrying {
+                    path: path.clone(),
+                    phase,
+                    attempt: retry.attempt,
+                    max_attempts: retry.max_attempts,
+                    error: retry.error.clone(),
+                    delay: retry.delay,
+                });
+            });
+        let result = task.execute().await;
+        let attempts = task.attempts();
+        let DownloadAttempt { transferred, error, .. } = task.into_inner();
+        match result {
+            TaskResult::SUCCESS(size) => {
+                emit(events, DownloadEvent::FileFinished { path: file.path.clone(), phase: file.phase, size });
+                let outcome = match attempts {
+                    1 => FileOutcome::Downloaded,
+                    n => FileOutcome::Retried((n - 1) as u16),
+                };
+                FileReport::new(file.clone(), outcome, transferred, start.elapsed())
+            }
+            TaskResult::ABORTED => {
+                log::debug!("Download of {} aborted", &file.path);
+                // cancelled between attempts the .part is kept for a resume that won't come
+                let _ = fs::remove_file(Self::part_path(Path::new(&file.path)));
+                emit(events, DownloadEvent::FileFailed {
+                    path: file.path.clone(),
+                    phase: file.phase,
+                    error: DownloadError::Aborted.to_string(),
+                });
+                FileReport::new(file.clone(), FileOutcome::Aborted, transferred, start.elapsed())
+            }
+            TaskResult::FAILURE(message) => {
+                let e = error.unwrap_or_else(|| DownloadError::Io(io::Error::other(message)));
+                log::error!("Failed to download {} after {} attempts: {}", &file.path, attempts, e);
+                emit(events, DownloadEvent::FileFailed {
+                    path: file.path.clone(),
+                    phase: file.phase,
+                    error: e.to_string(),
+                });
+                FileReport::new(file.clone(), FileOutcome::Failed(e), transferred, start.elapsed())
+            }
         }
-        */
-        Ok(())
     }
 
-    pub async fn download_file(
+    /// Downloads the file into `<dest>.part`, resuming it from where a previous attempt
+    /// left it when the source allows, and moves it into place once the checksum passes.
+    async fn try_download(
         file: &FileData,
-        client: Client,
-        progress: Option<Arc<RwLock<FileProgress>>>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> 
+        url: &str,
+        context: &DownloadContext,
+        transferred: &mut u64,
+    ) -> Result<u64, DownloadError>
     {
-        if Self::verify_file(&file) {
-            if let Some(p) = progress {
-                p.write().unwrap().set_progress((1,1));
-            }
-            return Ok(());
-        }
-        
-        log::debug!("Starting download of {}", &file.path);
-        let url = &file.url;
+        let DownloadContext { transport, events, cancel, limiter, concurrency, stall_timeout, .. } = context;
+        let events = events.as_ref();
+        let slot = tokio::select! {
+            slot = concurrency.acquire(url, file.download_priority()) => slot,
+            _ = cancel.cancelled() => return Err(DownloadError::Aborted),
+        };
+        log::debug!("Starting download of {} from {}", &file.path, url);
         let dest = Path::new(&file.path);
-        let response = match client.get(url).send().await {
-            Ok(r) => r,
-            Err(e) => {
-                // remove to vector
-                return Err(Box::new(e));
+        let part = Self::part_path(dest);
+
+        let mut existing = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+        if existing > 0 {
+            log::debug!("Resuming {} from byte {}", dest.display(), existing);
+        }
+        let mut requested = Instant::now();
+        let response = loop {
+            let fetched = tokio::select! {
+                response = tokio::time::timeout(*stall_timeout, transport.fetch(url, existing)) => match response {
+                    Ok(response) => response,
+                    Err(_) => return Err(Self::stalled(file, *stall_timeout, events)),
+                },
+                _ = cancel.cancelled() => return Err(DownloadError::Aborted),
+            };
+            match fetched {
+                // the whole file is already in the .part, e.g. the process died before the rename
+                Err(DownloadError::RangeNotSatisfiable) if existing > 0 => {
+                    if file.checksums.has_hashes() && file.checksums.verify_file(&part).is_ok() {
+                        log::debug!("{} was complete, finishing it", part.display());
+                        fs::rename(&part, dest)?;
+                        return Ok(existing);
+                    }
+                    log::warn!("Can't resume {}, downloading it again", dest.display());
+                    let _ = fs::remove_file(&part);
+                    existing = 0;
+                    requested = Instant::now();
+                }
+                fetched => break fetched?,
             }
         };
-        let total_size = response.content_length().unwrap_or(0);
-        let mut stream = response.bytes_stream();
-        let mut file = AsyncFile::create(dest).await?;
-        let mut total_writen = 0;
-
-        if let Some(progress) = progress.as_ref() {
-            progress
-                .write()
-                .unwrap()
-                .set_progress((0, total_size as usize));
+        let latency = requested.elapsed();
+
+        // a source that ignores the offset sends the whole file again
+        let resumed = existing > 0 && response.resumed;
+        let resumable = response.resumable;
+        let offset = if resumed { existing } else { 0 };
+        let total_size = response.content_length.unwrap_or(0) + offset;
+        let expected_size = file.expected_size();
+
+        // the size is known before the body, a wrong one is rejected without downloading it
+        if let Some(length) = response.content_length
+            && let Err(e) = file.checksums.verify_size(length + offset)
+        {
+            let _ = fs::remove_file(&part);
+            return Err(e);
         }
+        // the hashes are computed while streaming, a resumed file hashes its part first
+        let mut hasher = file.checksums.hasher();
+        if resumed {
+            hasher.update_from(&part)?;
+        }
+
+        let mut out = if resumed {
+            AsyncOpenOptions::new().append(true).open(&part).await?
+        } else {
+            AsyncFile::create(&part).await?
+        };
+        let mut total_writen = offset;
+        emit(events, DownloadEvent::FileStarted {
+            path: file.path.clone(),
+            phase: file.phase,
+            downloaded: offset,
+            total: total_size,
+            expected: file.expected_size(),
+        });
 
-        while let Some(item) = stream.next().await {
-            let chunk = item?;
-            file.write_all(&chunk).await?;
-            total_writen += chunk.len();
-            if let Some(progress) = progress.as_ref() {
-                progress.write().unwrap().set_actual_progress(total_writen);
+        let mut stream = response.body;
+        loop {
+            let next = async {
+                // the time spent waiting on the bandwidth limit is not a stall
+                let item = match tokio::time::timeout(*stall_timeout, stream.next()).await {
+                    Ok(item) => item,
+                    Err(_) => Some(Err(Self::stalled(file, *stall_timeout, events))),
+                };
+                if let Some(Ok(chunk)) = &item {
+                    limiter.acquire(chunk.len() as u64).await;
+                }
+                item
+            };
+            let item = tokio::select! {
+                item = next => item,
+                _ = cancel.cancelled() => {
+                    drop(out);
+                    let _ = fs::remove_file(&part);
+                    return Err(DownloadError::Aborted);
+                }
+            };
+            let Some(item) = item else { break };
+            let chunk = match item {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    out.flush().await?;
+                    if !resumable {
+                        let _ = fs::remove_file(&part);
+                    }
+                    return Err(e);
+                }
+            };
+            out.write_all(&chunk).await?;
+            hasher.update(&chunk);
+            total_writen += chunk.len() as u64;
+            if let Some(expected) = expected_size.filter(|expected| total_writen > *expected) {
+                drop(out);
+                let _ = fs::remove_file(&part);
+                return Err(DownloadError::SizeMismatch { expected, found: total_writen });
             }
+            *transferred += chunk.len() as u64;
+            emit(events, DownloadEvent::FileBytes {
+                path: file.path.clone(),
+                downloaded: total_writen,
+                total: total_size,
+            });
             let progress_percent = (total_writen as f64 / total_size as f64) * 100.0;
             log::debug!("{:?} - {:.2}%", dest.to_str(), progress_percent);
         }
-        file.flush().await?;
+        out.flush().await?;
+        drop(out);
+
+        if let Err(e) = hasher.verify(&file.checksums) {
+            let _ = fs::remove_file(&part);
+            // the .part may be left by an older run or another file, download it whole once
+            if resumed {
+                log::warn!("Verification of resumed {} failed: {}, downloading it again", dest.display(), e);
+                drop(slot);
+                return Box::pin(Self::try_download(file, url, context, transferred)).await;
+            }
+            log::warn!("Verification of {} failed: {}", dest.display(), e);
+            return Err(e);
+        }
+        fs::rename(&part, dest)?;
+        concurrency.on_success(latency, total_writen - offset);
+        Ok(total_writen)
+    }
 
-        /*
-        TODO:
-        Después de descargar el archivo, verifica también el checksum, y si falla:
-            reintenta la descarga (máx. 2 o 3 veces), si no, abortar
-        */
-        Ok(())
+    /// Publishes the stall of `file`, the attempt ends and goes to the retry logic
+    fn stalled(file: &FileData, timeout: Duration, events: Option<&EventSender>) -> DownloadError {
+        log::warn!("No data for {} in {:?}", &file.path, timeout);
+        emit(events, DownloadEvent::FileStalled {
+            path: file.path.clone(),
+            phase: file.phase,
+            timeout,
+        });
+        DownloadError::Stalled(timeout)
+    }
+
+    fn part_path(dest: &Path) -> PathBuf {
+        let mut name = dest.as_os_str().to_os_string();
+        name.push(PART_EXTENSION);
+        PathBuf::from(name)
+    }
+
+    /// Bytes the given files still need on disk. A file already in place with the expected
+    /// size is not counted, even if its checksum fails it is replaced by one of the same size.
+    pub fn required_space(files: &[FileData]) -> u64 {
+        files
+            .iter()
+            .map(|file| {
+                let Some(size) = file.expected_size() else { return 0 };
+                let dest = Path::new(&file.path);
+                if fs::metadata(dest).is_ok_and(|m| m.len() == size) {
+                    return 0;
+                }
+                let partial = fs::metadata(Self::part_path(dest)).map(|m| m.len()).unwrap_or(0);
+                size.saturating_sub(partial)
+            })
+            .sum()
+    }
+
+    /// Removes the `.part` files left by runs older than `max_age` in the directories the
+    /// downloader writes to, the saves and other files of the user are never walked.
+    pub fn clean_stale_parts(minecraft_path: &Path, max_age: Duration) -> usize {
+        ["versions", "libraries", "assets"]
+            .iter()
+            .map(|dir| Self::clean_stale_parts_in(&minecraft_path.join(dir), max_age))
+            .sum()
+    }
+
+    /// Entries that can't be read are skipped, the rest of the directory is still cleaned
+    fn clean_stale_parts_in(dir: &Path, max_age: Duration) -> usize {
+        let Ok(entries) = fs::read_dir(dir) else { return 0 };
+        let mut removed = 0;
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                removed += Self::clean_stale_parts_in(&path, max_age);
+                continue;
+            }
+            if !path.to_string_lossy().ends_with(PART_EXTENSION) {
+                continue;
+            }
+            let age = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .map(|modified| modified.elapsed().unwrap_or_default());
+            match age {
+                Ok(age) if age >= max_age => {
+                    log::debug!("Removing stale partial download {}", path.display());
+                    match fs::remove_file(&path) {
+                        Ok(()) => removed += 1,
+                        Err(e) => log::warn!("Failed to remove {}: {}", path.display(), e),
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!("Failed to read {}: {}", path.display(), e),
+            }
+        }
+        removed
     }
 
     fn verify_file(file: &FileData) -> bool {
         let dest = Path::new(&file.path);
         if dest.exists() {
-            return match (VersionVerifier::get_sha1(dest), &file.sha1) {
-                (Ok(local_sha1), Some(expected_sha1)) => {
-                    if &local_sha1 == expected_sha1 {
-                        log::info!(
-                            "File already exists and passed checksum: {}",
-                            dest.display()
-                        );
-                        true
-                    } else {
-                        log::warn!(
-                            "Checksum mismatch for {}. Expected: {}, Found: {}. Redownloading.",
-                            dest.display(),
-                            expected_sha1,
-                            local_sha1
-                        );
-                        false
-                    }
-                }
-                (Err(e), Some(_)) => {
-                    log::warn!(
-                        "Failed to compute checksum for {}: {}. Redownloading.",
-                        dest.display(),
-                        e
-                    );
-                    false
-                }
-                (_, None) => {
+            if !file.checksums.has_hashes() {
+                log::info!(
+                    "File exists but no checksum to verify: {}. Proceeding to redownload.",
+                    dest.display()
+                );
+                return false;
+            }
+            return match file.checksums.verify_file(dest) {
+                Ok(()) => {
                     log::info!(
-                        "File exists but no checksum to verify: {}. Proceeding to redownload.",
+                        "File already exists and passed checksum: {}",
                         dest.display()
                     );
+                    true
+                }
+                Err(e) => {
+                    log::warn!("{}: {}. Redownloading.", dest.display(), e);
                     false
                 }
             };
@@ -370,12 +941,6 @@ impl Downloader {
         false
     }
 
-    pub async fn clean_progress(&mut self) {
-        if let Some(progress) = &self.progress {
-            let mut lock = progress.lock().await;
-            lock.clean();
-        }
-    }
 }
 
 // +============================+
@@ -385,8 +950,15 @@ pub struct Builder {
     concurret_downloads: usize,
     connect_timeout: Duration,
     timeout: Duration,
-    retries: u16,
-    progress: Option<Arc<Mutex<DownloaderTracking>>>,
+    retry: RetryPolicy,
+    events: Option<EventSender>,
+    cancel: CancellationToken,
+    transport: Option<Arc<dyn Transport>>,
+    mirrors: Mirrors,
+    limiter: BandwidthLimiter,
+    adaptive_concurrency: bool,
+    max_connections_per_host: Option<usize>,
+    stall_timeout: Duration,
 }
 
 impl Default for Builder {
@@ -395,8 +967,17 @@ impl Default for Builder {
             concurret_downloads: 32,
             connect_timeout: Duration::from_secs(60),
             timeout: Duration::from_secs(180),
-            retries: 5,
-            progress: None,
+            retry: RetryPolicy::new()
+                .max_attempts(6)
+                .backoff(Duration::from_millis(250), Duration::from_secs(10)),
+            events: None,
+            cancel: CancellationToken::new(),
+            transport: None,
+            mirrors: Mirrors::default(),
+            limiter: BandwidthLimiter::unlimited(),
+            adaptive_concurrency: false,
+            max_connections_per_host: None,
+            stall_timeout: Duration::from_secs(30),
         }
     }
 }
@@ -418,12 +999,68 @@ impl Builder {
     }
 
     pub fn retries(&mut self, r: u16) -> &mut Self {
-        self.retries = r;
+        self.retry = self.retry.max_attempts(r as u32 + 1);
+        self
+    }
+
+    /// Attempts and waits between them for each file, `retries` only changes the attempts
+    pub fn retry_policy(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry = policy;
+        self
+    }
+
+    /// An attempt that receives no bytes for this long is dropped and retried
+    pub fn stall_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.stall_timeout = timeout;
+        self
+    }
+
+    /// Moves the connections between one and `concurret_downloads` following the network,
+    /// instead of always opening `concurret_downloads`
+    pub fn adaptive_concurrency(&mut self, adaptive: bool) -> &mut Self {
+        self.adaptive_concurrency = adaptive;
+        self
+    }
+
+    /// Caps the connections open to a single host
+    pub fn max_connections_per_host(&mut self, max: Option<usize>) -> &mut Self {
+        self.max_connections_per_host = max;
+        self
+    }
+
+    /// Publishes the download progress on the given channel
+    pub fn events(&mut self, sender: EventSender) -> &mut Self {
+        self.events = Some(sender);
+        self
+    }
+
+    /// Cancelling the token stops the in-flight downloads and removes their partial files
+    pub fn cancellation(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Source the files are fetched from, http and `file://` urls by default
+    pub fn transport(&mut self, transport: Arc<dyn Transport>) -> &mut Self {
+        self.transport = Some(transport);
         self
     }
 
-    pub fn progress(&mut self, p: Arc<Mutex<DownloaderTracking>>) -> &mut Self {
-        self.progress = Some(p);
+    /// Mirrors tried before the original url of each file
+    pub fn mirrors(&mut self, mirrors: Mirrors) -> &mut Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    /// Caps the combined rate of all the downloads, in bytes per second
+    pub fn bandwidth_limit(&mut self, bytes_per_second: Option<u64>) -> &mut Self {
+        self.limiter = BandwidthLimiter::new(bytes_per_second);
+        self
+    }
+
+    /// Shares a limiter with other downloaders, its rate can be changed while downloading
+    pub fn limiter(&mut self, limiter: BandwidthLimiter) -> &mut Self {
+        self.limiter = limiter;
         self
     }
 
@@ -436,14 +1073,32 @@ impl Builder {
     }
 
     pub fn build_with_client(&self, client: Client) -> io::Result<Downloader> {
+        self.build_with_transport(Arc::new(DefaultTransport::new(client)))
+    }
+
+    pub fn build_with_transport(&self, transport: Arc<dyn Transport>) -> io::Result<Downloader> {
         Ok(Downloader {
-            client,
+            context: DownloadContext {
+                transport,
+                retry: self.retry,
+                events: self.events.clone(),
+                cancel: self.cancel.clone(),
+                limiter: self.limiter.clone(),
+                concurrency: Arc::new(ConcurrencyControl::new(
+                    self.concurret_downloads,
+                    self.adaptive_concurrency,
+                    self.max_connections_per_host,
+                )),
+                stall_timeout: self.stall_timeout,
+            },
             concurrent_downloads: self.concurret_downloads,
-            retries: self.retries,
-            progress: self.progress.clone(),
+            mirrors: self.mirrors.clone(),
         })
     }
     pub fn build(&self) -> io::Result<Downloader> {
+        if let Some(transport) = &self.transport {
+            return self.build_with_transport(transport.clone());
+        }
         let client = self.build_client()?;
         self.build_with_client(client)
     }
@@ -455,28 +1110,164 @@ impl Builder {
 
 #[derive(Debug)]
 struct DownloadTask {
-    client: Client,
     file: FileData,
-    file_progress: Option<Arc<RwLock<FileProgress>>>,
-    global_progess: Option<Arc<Mutex<DownloaderTracking>>>,
+    context: DownloadContext,
+    report: Arc<Mutex<DownloadReport>>,
 }
 
 impl Task<()> for DownloadTask {
     async fn execute(&mut self) -> TaskResult<()> {
-        if let Some(progress) = self.global_progess.as_ref() {
-            if self.file_progress.is_none() {
-                self.file_progress = Some(Arc::new(RwLock::new(FileProgress::new(self.file.url.clone()))))
+        let file_report = Downloader::download_file(&self.file, &self.context).await;
+        let result = match file_report.outcome() {
+            FileOutcome::Failed(e) => TaskResult::FAILURE(e.to_string()),
+            FileOutcome::Aborted => TaskResult::ABORTED,
+            _ => TaskResult::SUCCESS(()),
+        };
+
+        self.report.lock().await.push(file_report);
+        result
+    }
+
+    fn is_abortable(&self) -> bool {
+        true
+    }
+
+    fn abort(&mut self) -> bool {
+        self.context.cancel.cancel();
+        true
+    }
+
+    /// Files of unknown size count as one kilobyte
+    fn weight(&self) -> f64 {
+        self.file.expected_size().unwrap_or(1024).max(1) as f64
+    }
+}
+
+/// A single try at downloading a file, each one from the next of its urls.
+/// `Downloader::download_file` retries it.
+struct DownloadAttempt<'a> {
+    file: &'a FileData,
+    context: &'a DownloadContext,
+    attempt: usize,
+    /// Bytes received by all the attempts
+    transferred: u64,
+    /// Error of the last failed attempt
+    error: Option<DownloadError>,
+}
+
+impl<'a> DownloadAttempt<'a> {
+    fn new(file: &'a FileData, context: &'a DownloadContext) -> Self {
+        Self { file, context, attempt: 0, transferred: 0, error: None }
+    }
+
+    /// Every url gets a try, after that only the errors another try could fix are retried
+    fn is_retryable(&self) -> bool {
+        self.attempt < self.file.urls().len() || self.error.as_ref().is_none_or(|e| e.is_retryable())
+    }
+}
+
+impl Task<u64> for DownloadAttempt<'_> {
+    async fn execute(&mut self) -> TaskResult<u64> {
+        let urls = self.file.urls();
+        let url = urls[self.attempt % urls.len()];
+        self.attempt += 1;
+        match Downloader::try_download(self.file, url, self.context, &mut self.transferred).await {
+            Ok(size) => TaskResult::SUCCESS(size),
+            Err(DownloadError::Aborted) => TaskResult::ABORTED,
+            Err(e) => {
+                self.context.concurrency.on_error(url, &e);
+                log::warn!("Download of {} from {} failed: {}", &self.file.path, url, e);
+                let message = e.to_string();
+                self.error = Some(e);
+                TaskResult::FAILURE(message)
             }
-            let fp = self.file_progress.clone().unwrap();
-            progress.lock().await.add_unit(fp);
         }
-        match Downloader::download_file(
-            &self.file,
-            self.client.clone(),
-            self.file_progress.clone(),
-        ).await {
-            Ok(r) => { TaskResult::SUCCESS(r)}
-            Err(e) => {TaskResult::FAILURE(e.to_string())}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::downloader::transport::{FetchResponse, MemoryTransport};
+    use futures_util::future::BoxFuture;
+    use sha1::{Digest, Sha1};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const URL: &str = "https://example.com/file.bin";
+
+    /// Counts the fetches, one per attempt
+    #[derive(Debug, Default)]
+    struct CountingTransport {
+        inner: MemoryTransport,
+        fetches: AtomicUsize,
+    }
+
+    impl Transport for CountingTransport {
+        fn fetch<'a>(&'a self, url: &'a str, offset: u64) -> BoxFuture<'a, Result<FetchResponse, DownloadError>> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            self.inner.fetch(url, offset)
         }
     }
+
+    /// Downloads `file` with three attempts and no waits, returns the outcome and the fetches made
+    async fn download_counting(transport: CountingTransport, file: &FileData) -> (FileReport, usize) {
+        let transport = Arc::new(transport);
+        let downloader = Downloader::builder()
+            .retry_policy(RetryPolicy::new().max_attempts(3).backoff(Duration::ZERO, Duration::ZERO))
+            .build_with_transport(transport.clone())
+            .unwrap();
+        let report = Downloader::download_file(file, &downloader.context).await;
+        (report, transport.fetches.load(Ordering::SeqCst))
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustacean-downloader-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn corrupt_part_is_downloaded_again_in_the_same_attempt() {
+        let content: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+        let mut transport = MemoryTransport::new();
+        transport.insert(URL, content.clone());
+        let downloader = Downloader::builder().retries(0).build_with_transport(Arc::new(transport)).unwrap();
+
+        let dir = temp_dir("corrupt-part");
+        let dest = dir.join("file.bin");
+        fs::write(Downloader::part_path(&dest), [0xaa; 100]).unwrap();
+        let sha1 = hex::encode(Sha1::digest(&content));
+        let file = FileData::new(dest.to_string_lossy().to_string(), URL.to_string(), Some(sha1)).size(10_000);
+
+        let report = Downloader::download_file(&file, &downloader.context).await;
+        assert!(matches!(report.outcome(), FileOutcome::Downloaded), "{:?}", report.outcome());
+        assert_eq!(fs::read(&dest).unwrap(), content);
+        assert!(!Downloader::part_path(&dest).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn mismatches_are_retried_from_a_single_url() {
+        let mut transport = CountingTransport::default();
+        transport.inner.insert(URL, vec![0u8; 5_000]);
+        let dir = temp_dir("mismatch");
+        let file = FileData::new(dir.join("file.bin").to_string_lossy().to_string(), URL.to_string(), None).size(10_000);
+
+        let (report, fetches) = download_counting(transport, &file).await;
+        assert!(matches!(report.outcome(), FileOutcome::Failed(DownloadError::SizeMismatch { .. })));
+        assert_eq!(fetches, 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn missing_local_files_are_not_retried() {
+        let dir = temp_dir("missing");
+        let file = FileData::new(dir.join("file.bin").to_string_lossy().to_string(), URL.to_string(), None);
+
+        let (report, fetches) = download_counting(CountingTransport::default(), &file).await;
+        assert!(matches!(report.outcome(), FileOutcome::Failed(DownloadError::Io(_))));
+        assert_eq!(fetches, 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,38 @@
+use crate::downloader::downloader::{DownloadPhase, DownloadState};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Capacity of the progress channel, a slow subscriber lags behind after this many events
+pub const EVENTS_CAPACITY: usize = 4096;
+
+/// Progress published by the downloader, subscribers fold it into a `DownloaderTracking`
+#[derive(Debug, Clone)]
+pub enum DownloadEvent {
+    PhaseChanged(DownloadState),
+    /// A batch of files was handed to the downloader, `bytes` only counts files of known size
+    Queued { phase: DownloadPhase, files: usize, bytes: u64 },
+    /// `downloaded` is non zero when a partial file is resumed, `expected` comes from the metadata
+    FileStarted { path: String, phase: DownloadPhase, downloaded: u64, total: u64, expected: Option<u64> },
+    FileBytes { path: String, downloaded: u64, total: u64 },
+    /// No bytes arrived for `timeout`, the attempt is dropped and retried
+    FileStalled { path: String, phase: DownloadPhase, timeout: Duration },
+    /// Attempt number `attempt` failed with `error`, the next one starts after `delay`
+    FileRetrying { path: String, phase: DownloadPhase, attempt: u32, max_attempts: u32, error: String, delay: Duration },
+    /// `size` is the size of the file on disk
+    FileFinished { path: String, phase: DownloadPhase, size: u64 },
+    FileFailed { path: String, phase: DownloadPhase, error: String },
+}
+
+pub type EventSender = broadcast::Sender<DownloadEvent>;
+pub type EventReceiver = broadcast::Receiver<DownloadEvent>;
+
+pub fn channel() -> (EventSender, EventReceiver) {
+    broadcast::channel(EVENTS_CAPACITY)
+}
+
+/// Publishes an event, nobody listening is not an error
+pub(crate) fn emit(sender: Option<&EventSender>, event: DownloadEvent) {
+    if let Some(sender) = sender {
+        let _ = sender.send(event);
+    }
+}
@@ -0,0 +1,82 @@
+use crate::launcher::launcher_config::LauncherConfig;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Token bucket shared by every download using it, the rate can be changed while downloading
+#[derive(Debug, Clone)]
+pub struct BandwidthLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes per second, `None` means unlimited
+    rate: Option<u64>,
+    tokens: f64,
+    last: Instant,
+}
+
+static GLOBAL: OnceLock<BandwidthLimiter> = OnceLock::new();
+
+impl BandwidthLimiter {
+    pub fn new(rate: Option<u64>) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                rate,
+                tokens: rate.unwrap_or(0) as f64,
+                last: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    /// Limiter shared by every install of the launcher, starts at `LauncherConfig::bandwidth_limit`
+    pub fn global() -> Self {
+        GLOBAL
+            .get_or_init(|| Self::new(LauncherConfig::import_config().bandwidth_limit))
+            .clone()
+    }
+
+    pub fn rate(&self) -> Option<u64> {
+        self.bucket.lock().unwrap().rate
+    }
+
+    pub fn set_rate(&self, rate: Option<u64>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+        bucket.rate = rate;
+        // forget the debt of the old rate, the new one applies from now
+        bucket.tokens = bucket.tokens.max(0.0);
+        log::info!("Bandwidth limit set to {:?} bytes/s", rate);
+    }
+
+    /// Waits until `bytes` can be transferred without exceeding the rate
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            let Some(rate) = bucket.rate else { return };
+            bucket.refill();
+            bucket.tokens -= bytes as f64;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / rate.max(1) as f64)
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        if let Some(rate) = self.rate {
+            let elapsed = now.duration_since(self.last).as_secs_f64();
+            // at most one second of burst
+            self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        }
+        self.last = now;
+    }
+}
@@ -0,0 +1,45 @@
+use reqwest::Url;
+use std::collections::HashMap;
+
+/// Ordered mirror base urls for each host, e.g.
+/// `resources.download.minecraft.net` -> `["https://mirror.lan/assets"]`
+#[derive(Debug, Clone, Default)]
+pub struct Mirrors {
+    hosts: HashMap<String, Vec<String>>,
+}
+
+impl Mirrors {
+    pub fn new(hosts: HashMap<String, Vec<String>>) -> Self {
+        Self { hosts }
+    }
+
+    pub fn add(&mut self, host: &str, base_url: &str) -> &mut Self {
+        self.hosts
+            .entry(host.to_string())
+            .or_default()
+            .push(base_url.to_string());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hosts.values().all(|m| m.is_empty())
+    }
+
+    /// Urls to try for `url`: its mirrors in order and the original url last
+    pub fn candidates(&self, url: &str) -> Vec<String> {
+        let mut urls = Vec::new();
+        if let Ok(parsed) = Url::parse(url) {
+            let mirrors = parsed.host_str().and_then(|host| self.hosts.get(host));
+            let mut path = parsed.path().to_string();
+            if let Some(query) = parsed.query() {
+                path.push('?');
+                path.push_str(query);
+            }
+            for base in mirrors.into_iter().flatten() {
+                urls.push(format!("{}{}", base.trim_end_matches('/'), path));
+            }
+        }
+        urls.push(url.to_string());
+        urls
+    }
+}
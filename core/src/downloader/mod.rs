@@ -1 +1,9 @@
+pub mod checksum;
+pub mod concurrency;
 pub mod downloader;
+pub mod events;
+pub mod limiter;
+pub mod mirrors;
+pub mod registry;
+pub mod report;
+pub mod transport;
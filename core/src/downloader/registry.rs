@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::watch;
+
+/// Destination paths being downloaded by any `Downloader` of the process
+static IN_FLIGHT: OnceLock<Mutex<HashMap<PathBuf, watch::Receiver<()>>>> = OnceLock::new();
+
+fn in_flight() -> &'static Mutex<HashMap<PathBuf, watch::Receiver<()>>> {
+    IN_FLIGHT.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn key(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+pub enum Claim {
+    /// Nobody else is downloading the file, it is ours until the guard drops
+    Owner(InFlightGuard),
+    /// Another download has the file, wait for it and claim again
+    Busy(InFlightWait),
+}
+
+/// Claims the download of `path` for the whole process
+pub fn claim(path: &Path) -> Claim {
+    let key = key(path);
+    let mut map = in_flight().lock().unwrap();
+    if let Some(receiver) = map.get(&key) {
+        return Claim::Busy(InFlightWait { receiver: receiver.clone() });
+    }
+    let (sender, receiver) = watch::channel(());
+    map.insert(key.clone(), receiver);
+    Claim::Owner(InFlightGuard { key, _sender: sender })
+}
+
+/// Releases the claim when dropped, waking up whoever waits for the same file
+pub struct InFlightGuard {
+    key: PathBuf,
+    _sender: watch::Sender<()>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        // out of the map before the sender drops, so woken waiters don't find it again
+        in_flight().lock().unwrap().remove(&self.key);
+    }
+}
+
+pub struct InFlightWait {
+    receiver: watch::Receiver<()>,
+}
+
+impl InFlightWait {
+    /// Resolves once the other download is over, whatever its outcome
+    pub async fn finished(mut self) {
+        while self.receiver.changed().await.is_ok() {}
+    }
+}
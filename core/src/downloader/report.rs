@@ -0,0 +1,239 @@
+use crate::downloader::checksum::HashAlgorithm;
+use crate::downloader::downloader::FileData;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::time::Duration;
+
+// +============================+
+//         DownloadError
+// +============================+
+
+/// Reason why a file could not be downloaded
+#[derive(Debug)]
+pub enum DownloadError {
+    Http(reqwest::Error),
+    Io(io::Error),
+    ChecksumMismatch { algorithm: HashAlgorithm, expected: String, found: String },
+    SizeMismatch { expected: u64, found: u64 },
+    /// The server answered 429 or 503, `retry_after` is its `Retry-After` header
+    Throttled { retry_after: Option<Duration> },
+    /// No bytes arrived within the stall timeout
+    Stalled(Duration),
+    /// The server answered 416 to a resume, the offset is at or past the end of the file
+    RangeNotSatisfiable,
+    /// The download was cancelled
+    Aborted,
+}
+
+impl Display for DownloadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::Http(e) => write!(f, "http error: {e}"),
+            DownloadError::Io(e) => write!(f, "io error: {e}"),
+            DownloadError::ChecksumMismatch { algorithm, expected, found } => {
+                write!(f, "{algorithm} mismatch, expected: {expected}, found: {found}")
+            }
+            DownloadError::SizeMismatch { expected, found } => {
+                write!(f, "size mismatch, expected: {expected} bytes, found: {found} bytes")
+            }
+            DownloadError::Throttled { retry_after: Some(d) } => {
+                write!(f, "throttled by the server, retry after {} s", d.as_secs())
+            }
+            DownloadError::Throttled { retry_after: None } => write!(f, "throttled by the server"),
+            DownloadError::Stalled(d) => write!(f, "stalled, no data for {:?}", d),
+            DownloadError::RangeNotSatisfiable => write!(f, "requested range not satisfiable"),
+            DownloadError::Aborted => write!(f, "aborted"),
+        }
+    }
+}
+
+impl DownloadError {
+    /// The error is a sign of too many connections rather than of a bad file
+    pub fn is_congestion(&self) -> bool {
+        match self {
+            DownloadError::Throttled { .. } | DownloadError::Stalled(_) => true,
+            DownloadError::Http(e) => {
+                e.is_timeout() || e.is_connect() || e.status().is_some_and(|s| s.is_server_error())
+            }
+            _ => false,
+        }
+    }
+}
+
+impl DownloadError {
+    /// Another attempt from the same source may succeed. A missing or forbidden file will be
+    /// the same next time, a body that doesn't match its checksums may have been cut or
+    /// corrupted on the way.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DownloadError::Http(e) => e.status().is_none_or(|s| {
+                !s.is_client_error() || matches!(s.as_u16(), 408 | 429)
+            }),
+            DownloadError::Io(e) => !matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied),
+            DownloadError::Aborted => false,
+            DownloadError::ChecksumMismatch { .. }
+            | DownloadError::SizeMismatch { .. }
+            | DownloadError::Throttled { .. }
+            | DownloadError::Stalled(_)
+            | DownloadError::RangeNotSatisfiable => true,
+        }
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(value: reqwest::Error) -> Self {
+        DownloadError::Http(value)
+    }
+}
+
+impl From<io::Error> for DownloadError {
+    fn from(value: io::Error) -> Self {
+        DownloadError::Io(value)
+    }
+}
+
+// +============================+
+//          FileReport
+// +============================+
+
+#[derive(Debug)]
+pub enum FileOutcome {
+    /// The file was already on disk and passed the checksum
+    Skipped,
+    Downloaded,
+    /// Downloaded after the given number of retries
+    Retried(u16),
+    Failed(DownloadError),
+    /// Cancelled while downloading, the partial file was removed
+    Aborted,
+}
+
+impl Display for FileOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileOutcome::Skipped => write!(f, "skipped"),
+            FileOutcome::Downloaded => write!(f, "downloaded"),
+            FileOutcome::Retried(n) => write!(f, "downloaded after {n} retries"),
+            FileOutcome::Failed(e) => write!(f, "failed: {e}"),
+            FileOutcome::Aborted => write!(f, "aborted"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FileReport {
+    file: FileData,
+    outcome: FileOutcome,
+    bytes: u64,
+    elapsed: Duration,
+}
+
+impl FileReport {
+    pub fn new(file: FileData, outcome: FileOutcome, bytes: u64, elapsed: Duration) -> Self {
+        Self { file, outcome, bytes, elapsed }
+    }
+
+    pub fn file(&self) -> &FileData {
+        &self.file
+    }
+
+    pub fn outcome(&self) -> &FileOutcome {
+        &self.outcome
+    }
+
+    /// Bytes received over the network, retries included
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self.outcome, FileOutcome::Failed(_))
+    }
+
+    /// The file is in place, downloaded now or before
+    pub fn is_done(&self) -> bool {
+        matches!(self.outcome, FileOutcome::Skipped | FileOutcome::Downloaded | FileOutcome::Retried(_))
+    }
+}
+
+// +============================+
+//         DownloadReport
+// +============================+
+
+/// Outcome of every file handed to the downloader
+#[derive(Debug, Default)]
+pub struct DownloadReport {
+    files: Vec<FileReport>,
+    aborted: bool,
+}
+
+impl DownloadReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, report: FileReport) {
+        self.files.push(report)
+    }
+
+    pub fn merge(&mut self, other: DownloadReport) {
+        self.files.extend(other.files);
+        self.aborted |= other.aborted;
+    }
+
+    pub fn set_aborted(&mut self) {
+        self.aborted = true
+    }
+
+    /// True when the download was cancelled, files that never started are listed as aborted
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    pub fn files(&self) -> &Vec<FileReport> {
+        &self.files
+    }
+
+    pub fn failed(&self) -> Vec<&FileReport> {
+        self.files.iter().filter(|f| f.is_failed()).collect()
+    }
+
+    pub fn is_success(&self) -> bool {
+        !self.aborted && !self.files.iter().any(|f| f.is_failed())
+    }
+
+    /// Every file the game needs to start is in place, only assets may be missing
+    pub fn is_launchable(&self) -> bool {
+        self.files
+            .iter()
+            .all(|f| f.is_done() || f.file.download_priority().is_optional())
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.bytes).sum()
+    }
+}
+
+impl Display for DownloadReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let count = |pred: fn(&FileOutcome) -> bool| self.files.iter().filter(|r| pred(&r.outcome)).count();
+        write!(
+            f,
+            "{} files: {} downloaded, {} retried, {} skipped, {} failed ({} bytes){}{}",
+            self.files.len(),
+            count(|o| matches!(o, FileOutcome::Downloaded)),
+            count(|o| matches!(o, FileOutcome::Retried(_))),
+            count(|o| matches!(o, FileOutcome::Skipped)),
+            count(|o| matches!(o, FileOutcome::Failed(_))),
+            self.bytes(),
+            if self.aborted { ", aborted" } else { "" },
+            if !self.is_success() && self.is_launchable() { ", launchable, assets incomplete" } else { "" }
+        )
+    }
+}
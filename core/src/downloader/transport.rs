@@ -0,0 +1,250 @@
+use crate::downloader::report::DownloadError;
+use bytes::Bytes;
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, BoxStream};
+use futures_util::{StreamExt, TryStreamExt};
+use reqwest::header::{ACCEPT_RANGES, RANGE, RETRY_AFTER};
+use reqwest::{Client, StatusCode, Url};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncSeekExt;
+use tokio_util::io::ReaderStream;
+
+// +============================+
+//           Transport
+// +============================+
+
+/// Source the downloader fetches files from
+pub trait Transport: Send + Sync + Debug {
+    /// Fetches `url`, skipping the first `offset` bytes when the source supports it
+    fn fetch<'a>(&'a self, url: &'a str, offset: u64) -> BoxFuture<'a, Result<FetchResponse, DownloadError>>;
+
+    /// Fetches the whole body of `url` into memory
+    fn fetch_bytes<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Vec<u8>, DownloadError>> {
+        Box::pin(async move {
+            let response = self.fetch(url, 0).await?;
+            let mut body = response.body;
+            let mut content = Vec::with_capacity(response.content_length.unwrap_or(0) as usize);
+            while let Some(chunk) = body.next().await {
+                content.extend_from_slice(&chunk?);
+            }
+            Ok(content)
+        })
+    }
+}
+
+pub struct FetchResponse {
+    /// The body starts at the requested offset instead of the beginning of the file
+    pub resumed: bool,
+    /// A later fetch of the same url can be resumed from an offset
+    pub resumable: bool,
+    /// Length of `body`
+    pub content_length: Option<u64>,
+    pub body: BoxStream<'static, Result<Bytes, DownloadError>>,
+}
+
+impl FetchResponse {
+    fn from_bytes(content: Bytes, offset: u64) -> Self {
+        let offset = (offset as usize).min(content.len());
+        let body = content.slice(offset..);
+        Self {
+            resumed: offset > 0,
+            resumable: true,
+            content_length: Some(body.len() as u64),
+            body: stream::once(async move { Ok(body) }).boxed(),
+        }
+    }
+}
+
+// +============================+
+//         HttpTransport
+// +============================+
+
+#[derive(Debug, Clone, Default)]
+pub struct HttpTransport {
+    client: Client,
+}
+
+impl HttpTransport {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn fetch<'a>(&'a self, url: &'a str, offset: u64) -> BoxFuture<'a, Result<FetchResponse, DownloadError>> {
+        Box::pin(async move {
+            let mut request = self.client.get(url);
+            if offset > 0 {
+                request = request.header(RANGE, format!("bytes={}-", offset));
+            }
+            let response = request.send().await?;
+            if matches!(response.status(), StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE) {
+                // only the delay in seconds form, an http date is treated as no hint
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .map(Duration::from_secs);
+                return Err(DownloadError::Throttled { retry_after });
+            }
+            if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+                return Err(DownloadError::RangeNotSatisfiable);
+            }
+            let response = response.error_for_status()?;
+
+            // 206 means the server honoured the range, anything else sends the whole file again
+            let resumed = offset > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+            let resumable = resumed || response
+                .headers()
+                .get(ACCEPT_RANGES)
+                .is_some_and(|v| v.as_bytes() == b"bytes");
+            Ok(FetchResponse {
+                resumed,
+                resumable,
+                content_length: response.content_length(),
+                body: response.bytes_stream().map_err(DownloadError::from).boxed(),
+            })
+        })
+    }
+}
+
+// +============================+
+//         FileTransport
+// +============================+
+
+/// Reads `file://` urls and plain paths from the local filesystem
+#[derive(Debug, Clone, Default)]
+pub struct FileTransport;
+
+impl FileTransport {
+    fn path_of(url: &str) -> io::Result<PathBuf> {
+        if !url.starts_with("file:") {
+            return Ok(PathBuf::from(url));
+        }
+        Url::parse(url)
+            .ok()
+            .and_then(|u| u.to_file_path().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid file url {}", url)))
+    }
+
+    async fn open(path: &Path, offset: u64) -> Result<FetchResponse, DownloadError> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+        let offset = offset.min(len);
+        if offset > 0 {
+            file.seek(io::SeekFrom::Start(offset)).await?;
+        }
+        Ok(FetchResponse {
+            resumed: offset > 0,
+            resumable: true,
+            content_length: Some(len - offset),
+            body: ReaderStream::new(file).map_err(DownloadError::from).boxed(),
+        })
+    }
+}
+
+impl Transport for FileTransport {
+    fn fetch<'a>(&'a self, url: &'a str, offset: u64) -> BoxFuture<'a, Result<FetchResponse, DownloadError>> {
+        Box::pin(async move { Self::open(&Self::path_of(url)?, offset).await })
+    }
+}
+
+// +============================+
+//       LocalDirTransport
+// +============================+
+
+/// Serves urls from a directory laid out as `<root>/<host>/<path>`, for air-gapped installs
+#[derive(Debug, Clone)]
+pub struct LocalDirTransport {
+    root: PathBuf,
+}
+
+impl LocalDirTransport {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn path_of(&self, url: &str) -> io::Result<PathBuf> {
+        let parsed = Url::parse(url)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid url {}: {}", url, e)))?;
+        let mut path = self.root.join(parsed.host_str().unwrap_or_default());
+        for segment in parsed.path_segments().into_iter().flatten() {
+            // never escape the root directory
+            if segment.is_empty() || segment == "." || segment == ".." {
+                continue;
+            }
+            path.push(segment);
+        }
+        Ok(path)
+    }
+}
+
+impl Transport for LocalDirTransport {
+    fn fetch<'a>(&'a self, url: &'a str, offset: u64) -> BoxFuture<'a, Result<FetchResponse, DownloadError>> {
+        Box::pin(async move { FileTransport::open(&self.path_of(url)?, offset).await })
+    }
+}
+
+// +============================+
+//        MemoryTransport
+// +============================+
+
+/// Serves files from memory, keyed by their url
+#[derive(Debug, Clone, Default)]
+pub struct MemoryTransport {
+    files: HashMap<String, Bytes>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, url: impl Into<String>, content: impl Into<Bytes>) -> &mut Self {
+        self.files.insert(url.into(), content.into());
+        self
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn fetch<'a>(&'a self, url: &'a str, offset: u64) -> BoxFuture<'a, Result<FetchResponse, DownloadError>> {
+        Box::pin(async move {
+            match self.files.get(url) {
+                Some(content) => Ok(FetchResponse::from_bytes(content.clone(), offset)),
+                None => Err(io::Error::new(io::ErrorKind::NotFound, format!("{} not found", url)).into()),
+            }
+        })
+    }
+}
+
+// +============================+
+//        DefaultTransport
+// +============================+
+
+/// Reads `file://` urls from disk and everything else over http
+#[derive(Debug, Clone, Default)]
+pub struct DefaultTransport {
+    http: HttpTransport,
+    file: FileTransport,
+}
+
+impl DefaultTransport {
+    pub fn new(client: Client) -> Self {
+        Self { http: HttpTransport::new(client), file: FileTransport }
+    }
+}
+
+impl Transport for DefaultTransport {
+    fn fetch<'a>(&'a self, url: &'a str, offset: u64) -> BoxFuture<'a, Result<FetchResponse, DownloadError>> {
+        if url.starts_with("file:") {
+            self.file.fetch(url, offset)
+        } else {
+            self.http.fetch(url, offset)
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use md5::{Digest, Md5};
+use std::collections::HashMap;
+
+// +============================+
+//        LaunchVariables
+// +============================+
+
+/// Values of the `${...}` placeholders of the version json arguments
+#[derive(Debug, Clone, Default)]
+pub struct LaunchVariables {
+    values: HashMap<String, String>,
+}
+
+impl LaunchVariables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.values.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|v| v.as_str())
+    }
+
+    /// Replaces every known placeholder of `argument`, the unknown ones are left as they are
+    pub fn substitute(&self, argument: &str) -> String {
+        let mut result = String::with_capacity(argument.len());
+        let mut rest = argument;
+        while let Some(start) = rest.find("${") {
+            result.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}') else {
+                rest = &rest[start..];
+                break;
+            };
+            let name = &rest[start + 2..start + end];
+            match self.get(name) {
+                Some(value) => result.push_str(value),
+                None => {
+                    log::warn!("No value for the launch argument placeholder {}", name);
+                    result.push_str(&rest[start..=start + end]);
+                }
+            }
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        result
+    }
+
+    pub fn substitute_all(&self, arguments: &[String]) -> Vec<String> {
+        arguments.iter().map(|a| self.substitute(a)).collect()
+    }
+}
+
+// +============================+
+//            Helpers
+// +============================+
+
+/// Splits arguments typed by the user, like the `javaArgs` of a profile, on whitespace.
+/// Quoted parts stay in one argument without their quotes. `\` only escapes a quote, so
+/// windows paths like `C:\dumps` or `\\server\share` keep their backslashes.
+pub fn split_arguments(arguments: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut quote: Option<char> = None;
+    let mut chars = arguments.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', q) if q != Some('\'') && chars.peek().is_some_and(|n| *n == '"' || *n == '\'') => {
+                current.extend(chars.next());
+                in_argument = true;
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => current.push(c),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                in_argument = true;
+            }
+            (c, None) if c.is_whitespace() => {
+                if in_argument {
+                    args.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            (c, None) => {
+                current.push(c);
+                in_argument = true;
+            }
+        }
+    }
+    if in_argument {
+        args.push(current);
+    }
+    args
+}
+
+/// Uuid of an offline player, the one the game and offline mode servers give it: java's
+/// `UUID.nameUUIDFromBytes("OfflinePlayer:<name>")`
+pub fn offline_uuid(username: &str) -> String {
+    let hash = Md5::digest(format!("OfflinePlayer:{}", username).as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hash);
+    bytes[6] = (bytes[6] & 0x0f) | 0x30; // version 3
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // IETF variant
+    let hex = hex::encode(bytes);
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn substitutes_known_placeholders() {
+        let variables = LaunchVariables::new().set("version_name", "1.21").set("game_directory", "/mc");
+        assert_eq!(variables.substitute("${version_name}"), "1.21");
+        assert_eq!(variables.substitute("-Dpath=${game_directory}/${version_name}"), "-Dpath=/mc/1.21");
+        assert_eq!(variables.substitute("--demo"), "--demo");
+    }
+
+    #[test]
+    fn leaves_unknown_and_unclosed_placeholders() {
+        let variables = LaunchVariables::new().set("a", "x");
+        assert_eq!(variables.substitute("${quickPlayPath}"), "${quickPlayPath}");
+        assert_eq!(variables.substitute("${a}${"), "x${");
+    }
+
+    #[test]
+    fn substituted_values_are_not_expanded_again() {
+        let variables = LaunchVariables::new().set("a", "${b}").set("b", "y");
+        assert_eq!(variables.substitute("${a}"), "${b}");
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(split_arguments("  -Xmx2G   -XX:+UseG1GC "), args(&["-Xmx2G", "-XX:+UseG1GC"]));
+        assert!(split_arguments("   ").is_empty());
+    }
+
+    #[test]
+    fn keeps_quoted_arguments_together() {
+        assert_eq!(
+            split_arguments(r#"-Dfoo="a b" '-Dbar=c d' """#),
+            args(&["-Dfoo=a b", "-Dbar=c d", ""])
+        );
+    }
+
+    #[test]
+    fn keeps_windows_backslashes() {
+        assert_eq!(
+            split_arguments(r#"-XX:HeapDumpPath=C:\dumps "-Dx=C:\Program Files\y" -Dshare=\\server\share"#),
+            args(&[r"-XX:HeapDumpPath=C:\dumps", r"-Dx=C:\Program Files\y", r"-Dshare=\\server\share"])
+        );
+    }
+
+    #[test]
+    fn backslash_escapes_quotes() {
+        assert_eq!(split_arguments(r#"-Dmsg="say \"hi\"" -Da=\'b"#), args(&[r#"-Dmsg=say "hi""#, "-Da='b"]));
+    }
+
+    #[test]
+    fn offline_uuid_matches_java() {
+        // UUID.nameUUIDFromBytes("OfflinePlayer:Notch".getBytes(UTF_8))
+        assert_eq!(offline_uuid("Notch"), "b50ad385-829d-3141-a216-7e7d7539ba7f");
+        assert_ne!(offline_uuid("notch"), offline_uuid("Notch"));
+    }
+}
@@ -1,5 +1,9 @@
 use std::path::Path;
 use std::process::Stdio;
+use crate::launcher::arguments::{self, LaunchVariables};
+use crate::versions::assets;
+use crate::versions::natives;
+use crate::versions::rules::LaunchContext;
 use crate::versions::version_json::{VersionJson};
 use crate::launcher::launcher_config::{LauncherConfig, LauncherProfiles, Profile};
 use crate::versions::Version;
@@ -10,7 +14,9 @@ pub struct MinecraftLauncher {
     user: Box<dyn User>,
     launcher_config: LauncherConfig,
     version_json: VersionJson,
-    profiles: Option<LauncherProfiles>
+    profiles: Option<LauncherProfiles>,
+    /// What the rules of the version json are evaluated against
+    context: LaunchContext,
 }
 
 impl MinecraftLauncher {
@@ -31,12 +37,31 @@ impl MinecraftLauncher {
         } else { None };
 
         log::debug!("Selected Profile {:?}", profile);
-        
-        let jvm_args = self.build_jvm_args(profile);
+
+        // installs made before the natives were extracted by the installer
+        if !natives::natives_path(minecraft_path, &self.version.name()).exists() {
+            natives::extract_natives(&self.version_json, minecraft_path, &self.version.name(), &self.context)?;
+        }
+
+        // the old asset indexes are read by name, `map_to_resources` ones from the game directory
+        let game_dir = self.game_dir(profile);
+        let index_id = self.version_json.get_asset_index().id;
+        let game_assets = match self.version_json.get_assets_json() {
+            Ok(assets_json) => {
+                assets::reconcile_assets(&assets_json, minecraft_path, &index_id, &game_dir)?;
+                assets::game_assets_path(&assets_json, minecraft_path, &index_id, &game_dir)
+            }
+            Err(e) => {
+                log::warn!("{e}, the game starts without the assets laid out");
+                self.launcher_config.assets_path()
+            }
+        };
+
         let classpath = self.build_classpath(client_jar.as_path());
+        let variables = self.build_variables(profile, classpath, &game_assets);
+        let jvm_args = self.build_jvm_args(profile, &variables);
         let main_class = self.version_json.get_main_class();
-        let game_args = self.build_game_args();
-        let auth_args = self.build_auth_args();
+        let game_args = self.build_game_args(&variables);
         
         log::debug!(
             "Launching minecraft with:
@@ -56,10 +81,8 @@ impl MinecraftLauncher {
         command.env("__GLX_VENDOR_LIBRARY_NAME", "nvidia");
         command
             .args(jvm_args)
-            .arg("-cp").arg(classpath) // Classpath con `client.jar` y bibliotecas
             .arg(main_class) // Clase principal del cliente
             .args(game_args)
-            .args(auth_args)
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit());
         log::debug!("Command created {:?}", command);
@@ -74,62 +97,81 @@ impl MinecraftLauncher {
         Ok(())
     }
 
-    fn build_jvm_args(&self, profile: Option<&Profile>) -> Vec<String> {
-        let mut vec = vec!["-Djava.library.path=./natives".to_string()]; 
-        if let Some(profile) = profile {
-            if let Some(profile_args) = &profile.java_args {
-                let mut args: Vec<String> = profile_args
-                    .split_whitespace()
-                    .map(|s| s.to_string())
-                    .collect();
-                vec.append(&mut args);
-            }
+    /// Values of the placeholders of the version json arguments
+    fn build_variables(&self, profile: Option<&Profile>, classpath: String, game_assets: &Path) -> LaunchVariables {
+        let version_name = self.version.name();
+        let game_dir = self.game_dir(profile);
+        let natives_dir = natives::natives_path(&self.launcher_config.minecraft_path, &version_name);
+        let assets_dir = self.launcher_config.assets_path();
+        let username = self.user.username();
+        let token = match self.user.token() {
+            token if token.is_empty() => "0".to_string(),
+            token => token,
+        };
+        let resolution = profile.and_then(|p| p.resolution.clone());
+
+        LaunchVariables::new()
+            .set("auth_player_name", username.as_str())
+            .set("auth_uuid", arguments::offline_uuid(&username))
+            .set("auth_access_token", token.as_str())
+            .set("auth_session", token)
+            .set("auth_xuid", "0")
+            .set("clientid", "0")
+            .set("user_type", "legacy")
+            .set("user_properties", "{}")
+            .set("version_name", version_name.as_str())
+            .set("version_type", self.version_json.get_type().as_str())
+            .set("game_directory", game_dir)
+            .set("assets_root", assets_dir.display().to_string())
+            .set("game_assets", game_assets.display().to_string())
+            .set("assets_index_name", self.version_json.get_asset_index().id)
+            .set("natives_directory", natives_dir.display().to_string())
+            .set("library_directory", self.launcher_config.libraries_path().display().to_string())
+            .set("classpath_separator", Self::classpath_separator())
+            .set("classpath", classpath)
+            .set("launcher_name", "rustacean-launcher")
+            .set("launcher_version", env!("CARGO_PKG_VERSION"))
+            .set("resolution_width", resolution.as_ref().map(|r| r.width.to_string()).unwrap_or_default())
+            .set("resolution_height", resolution.map(|r| r.height.to_string()).unwrap_or_default())
+    }
+
+    /// The `javaArgs` of the profile, then the jvm arguments of the version json
+    fn build_jvm_args(&self, profile: Option<&Profile>, variables: &LaunchVariables) -> Vec<String> {
+        let mut args = profile
+            .and_then(|p| p.java_args.as_deref())
+            .map(arguments::split_arguments)
+            .unwrap_or_default();
+        let mut jvm = self.version_json.get_arguments().jvm_values(&self.context);
+        if jvm.is_empty() {
+            jvm = ["-Djava.library.path=${natives_directory}", "-cp", "${classpath}"]
+                .iter()
+                .map(|a| a.to_string())
+                .collect();
         }
-        vec
+        args.append(&mut variables.substitute_all(&jvm));
+        args
     }
 
-    fn build_game_args(&self) -> Vec<String> {
-        let version_name = &self.version.name();
-        let game_dir = &self.launcher_config.minecraft_path;
-        let assets_dir = &self.launcher_config.assets_path(); 
-        let index_clone = self.version_json.get_asset_index().clone();
-        let assets_index = &format!("{}", index_clone.id);
-        let version_type = "release"; // TODO: VersonType to_str()
-        let args: Vec<&str> = vec![
-            "--version", version_name,
-            "--gameDir", game_dir,
-            "--assetsDir", assets_dir.as_path().to_str().unwrap(),
-            "--assetIndex", assets_index,
-            "--versionType", version_type
-        ];
-        // TODO: Parse or use a macro
-
-        args.iter().map(|a| a.to_string()).collect()
+    fn build_game_args(&self, variables: &LaunchVariables) -> Vec<String> {
+        let game = self.version_json.get_arguments().game_values(&self.context);
+        variables.substitute_all(&game)
     }
 
-    fn build_auth_args(&self) -> Vec<String> {
-        let username = &self.user.username();
-        let auth_uuid = "what?";
-        let auth_access_token = "i haven't that";
-        let client_id= "where client_id is obtained?";
-        let auth_xuid= "sad";
-        let user_type= "legacy";
-        let args = vec![
-            "--username", username,
-            "--uuid", auth_uuid,
-            "--accessToken", auth_access_token,
-            "--clientId", client_id,
-            "--xuid", auth_xuid,
-            "--userType", user_type,
-        ];
-        args.iter().map(|a| a.to_string()).collect()
+    /// The `gameDir` of the profile, the minecraft directory by default
+    fn game_dir(&self, profile: Option<&Profile>) -> String {
+        profile
+            .and_then(|p| p.game_dir.clone())
+            .unwrap_or_else(|| self.launcher_config.minecraft_path.clone())
     }
 
-    fn build_classpath(&self, client_jar_path: &Path) -> String {
+    fn classpath_separator() -> &'static str {
         // Delimitador para classpath: `:` en Unix y `;` en Windows
-        let delimiter = if cfg!(target_os = "windows") { ";" } else { ":" };
+        if cfg!(target_os = "windows") { ";" } else { ":" }
+    }
 
-        let libraries = self.version_json.get_libraries_path(&self.launcher_config.minecraft_path);
+    fn build_classpath(&self, client_jar_path: &Path) -> String {
+        let delimiter = Self::classpath_separator();
+        let libraries = self.version_json.get_libraries_path(&self.launcher_config.minecraft_path, &self.context);
         let mut classpath = libraries.join(delimiter);
         classpath.push_str(delimiter);
         classpath.push_str(client_jar_path.to_str().unwrap());
@@ -184,13 +226,21 @@ impl MinecraftBuilder {
             .map_err(|e| format!("Failed to load version JSON: {e}"))?;
 
         let profiles = LauncherProfiles::import_profiles();
+        let custom_resolution = profiles
+            .as_ref()
+            .and_then(|p| p.selected_profile())
+            .is_some_and(|p| p.resolution.is_some());
+        let context = LaunchContext::current()
+            .is_demo_user(false)
+            .has_custom_resolution(custom_resolution);
 
         Ok(MinecraftLauncher {
             version,
             user,
             launcher_config,
             version_json,
-            profiles
+            profiles,
+            context,
         })
     }
 }
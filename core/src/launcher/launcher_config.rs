@@ -3,12 +3,30 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::downloader::mirrors::Mirrors;
+use crate::downloader::transport::{LocalDirTransport, Transport};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LauncherConfig {
     pub minecraft_path: String,
     pub version_manifest_link: String,
     pub ui: Ui,
+    /// Directory with a `<host>/<path>` copy of the remote files, used instead of the network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_source: Option<String>,
+    /// Mirror base urls for each host, tried in order before the original url
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub mirrors: HashMap<String, Vec<String>>,
+    /// Combined download rate cap in bytes per second, unlimited when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bandwidth_limit: Option<u64>,
+    /// Cap of simultaneous connections to a single host, unlimited when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connections_per_host: Option<usize>,
+    /// Seconds without receiving data after which a download attempt is retried
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stall_timeout: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -48,6 +66,11 @@ impl LauncherConfig {
             minecraft_path: "Minecraft".to_string(),
             version_manifest_link: "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json".to_string(),
             ui: Ui::TUI,
+            local_source: None,
+            mirrors: HashMap::new(),
+            bandwidth_limit: None,
+            max_connections_per_host: None,
+            stall_timeout: None,
         }
     }
 
@@ -56,6 +79,20 @@ impl LauncherConfig {
     }
 }
 
+// Sources
+impl LauncherConfig {
+    /// Transport serving `local_source`, `None` means the default network transport
+    pub fn local_transport(&self) -> Option<Arc<dyn Transport>> {
+        self.local_source
+            .as_ref()
+            .map(|root| Arc::new(LocalDirTransport::new(root)) as Arc<dyn Transport>)
+    }
+
+    pub fn mirrors(&self) -> Mirrors {
+        Mirrors::new(self.mirrors.clone())
+    }
+}
+
 // Path getters
 impl LauncherConfig {
     pub fn minecraft_path(&self) -> PathBuf {
@@ -1,2 +1,3 @@
+pub mod arguments;
 pub mod launcher_config;
 pub mod launcher;
\ No newline at end of file
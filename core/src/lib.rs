@@ -2,4 +2,4 @@ pub mod downloader;
 pub mod launcher;
 pub mod users;
 pub mod versions;
-mod tasks;
\ No newline at end of file
+pub mod tasks;
\ No newline at end of file
@@ -0,0 +1,191 @@
+use crate::tasks::progress::Progress;
+use crate::tasks::tasks::{CancellationToken, Task, TaskResult};
+use futures_util::future::BoxFuture;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Index of a task in its `TaskGraph`
+pub type TaskId = usize;
+
+type BoxedTask<T> = Box<dyn FnOnce(Option<Progress>) -> BoxFuture<'static, TaskResult<T>> + Send>;
+
+struct Node<T> {
+    name: String,
+    weight: f64,
+    dependencies: Vec<TaskId>,
+    task: Option<BoxedTask<T>>,
+}
+
+/// Tasks that run as soon as the tasks they depend on succeed, at most `max_concurrent_tasks`
+/// at once. A task whose dependency fails or is aborted is aborted without running, the
+/// tasks that don't depend on it keep going.
+///
+/// Dependencies have to be added before their dependents, so the graph can't have cycles.
+pub struct TaskGraph<T> {
+    nodes: Vec<Node<T>>,
+    max_concurrent_tasks: usize,
+    cancel: CancellationToken,
+    progress: Option<Progress>,
+}
+
+impl<T: Send + 'static> TaskGraph<T> {
+    pub fn new(max_concurrent_tasks: usize) -> Self {
+        Self { nodes: Vec::new(), max_concurrent_tasks, cancel: CancellationToken::new(), progress: None }
+    }
+
+    /// Adds a task that runs once every task in `dependencies` succeeded
+    pub fn add_task<S: Task<T> + 'static>(&mut self, name: &str, task: S, dependencies: &[TaskId]) -> TaskId {
+        assert!(
+            dependencies.iter().all(|d| *d < self.nodes.len()),
+            "dependencies of {} must be added before it",
+            name
+        );
+        let weight = task.weight();
+        let task: BoxedTask<T> = Box::new(move |progress| {
+            let mut task = task;
+            if let Some(progress) = progress {
+                task.attach_progress(progress);
+            }
+            Box::pin(async move { task.execute().await })
+        });
+        self.nodes.push(Node {
+            name: name.to_string(),
+            weight,
+            dependencies: dependencies.to_vec(),
+            task: Some(task),
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Aborts the tasks not started yet once the token is cancelled, running tasks are
+    /// expected to watch the same token, clean up and return `TaskResult::ABORTED`
+    pub fn cancellation(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Reports into `progress`, one child scope per task named after it and weighted by `Task::weight`
+    pub fn progress(&mut self, progress: Progress) -> &mut Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub async fn run(&mut self) -> GraphResults<T> {
+        let count = self.nodes.len();
+        let mut dependents: Vec<Vec<TaskId>> = vec![Vec::new(); count];
+        let mut pending: Vec<usize> = Vec::with_capacity(count);
+        for (id, node) in self.nodes.iter().enumerate() {
+            pending.push(node.dependencies.len());
+            for dependency in &node.dependencies {
+                dependents[*dependency].push(id);
+            }
+        }
+        let mut results: Vec<Option<TaskResult<T>>> = (0..count).map(|_| None).collect();
+        let mut ready: VecDeque<TaskId> = (0..count).filter(|id| pending[*id] == 0).collect();
+        if let Some(progress) = &self.progress {
+            progress.set_total(self.nodes.iter().map(|n| n.weight).sum());
+        }
+
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrent_tasks.max(1)));
+        let mut set = tokio::task::JoinSet::new();
+        let mut running: HashMap<tokio::task::Id, TaskId> = HashMap::new();
+        loop {
+            while let Some(id) = ready.pop_front() {
+                if results[id].is_some() {
+                    continue;
+                }
+                let Some(task) = self.nodes[id].task.take() else { continue };
+                if self.cancel.is_cancelled() {
+                    Self::abort_from(id, &self.nodes, &dependents, &mut results);
+                    continue;
+                }
+                log::debug!("Starting task {}", self.nodes[id].name);
+                let semaphore = semaphore.clone();
+                let step = self.progress.as_ref().map(|p| p.child(&self.nodes[id].name, self.nodes[id].weight));
+                let handle = set.spawn(async move {
+                    let _permit = semaphore.acquire_owned().await.unwrap();
+                    // never dropped midway, a task cut off can't remove its partial work
+                    let result = task(step.clone()).await;
+                    if let Some(step) = step {
+                        step.finish();
+                    }
+                    result
+                });
+                running.insert(handle.id(), id);
+            }
+
+            let Some(joined) = set.join_next_with_id().await else { break };
+            let (id, result) = match joined {
+                Ok((task_id, result)) => (running[&task_id], result),
+                Err(e) => {
+                    log::error!("task panicked: {}", e);
+                    (running[&e.id()], TaskResult::FAILURE(e.to_string()))
+                }
+            };
+            let succeeded = matches!(result, TaskResult::SUCCESS(_));
+            match &result {
+                TaskResult::SUCCESS(_) => log::debug!("Task {} finished", self.nodes[id].name),
+                TaskResult::FAILURE(e) => log::error!("Task {} failed: {}", self.nodes[id].name, e),
+                TaskResult::ABORTED => log::debug!("Task {} aborted", self.nodes[id].name),
+            }
+            results[id] = Some(result);
+            for dependent in &dependents[id] {
+                if !succeeded {
+                    Self::abort_from(*dependent, &self.nodes, &dependents, &mut results);
+                    continue;
+                }
+                pending[*dependent] -= 1;
+                if pending[*dependent] == 0 && results[*dependent].is_none() {
+                    ready.push_back(*dependent);
+                }
+            }
+        }
+
+        GraphResults {
+            names: self.nodes.iter().map(|n| n.name.clone()).collect(),
+            results: results.into_iter().map(|r| r.unwrap_or(TaskResult::ABORTED)).collect(),
+        }
+    }
+
+    /// Aborts `id` and everything that depends on it
+    fn abort_from(
+        id: TaskId,
+        nodes: &[Node<T>],
+        dependents: &[Vec<TaskId>],
+        results: &mut [Option<TaskResult<T>>],
+    ) {
+        if results[id].is_some() {
+            return;
+        }
+        log::debug!("Task {} aborted, a dependency did not succeed", nodes[id].name);
+        results[id] = Some(TaskResult::ABORTED);
+        for dependent in &dependents[id] {
+            Self::abort_from(*dependent, nodes, dependents, results);
+        }
+    }
+}
+
+/// Result of every task of a `TaskGraph`, by `TaskId`
+pub struct GraphResults<T> {
+    names: Vec<String>,
+    results: Vec<TaskResult<T>>,
+}
+
+impl<T> GraphResults<T> {
+    pub fn get(&self, id: TaskId) -> &TaskResult<T> {
+        &self.results[id]
+    }
+
+    pub fn succeeded(&self, id: TaskId) -> bool {
+        matches!(self.results[id], TaskResult::SUCCESS(_))
+    }
+
+    pub fn is_success(&self) -> bool {
+        (0..self.results.len()).all(|id| self.succeeded(id))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TaskResult<T>)> {
+        self.names.iter().map(|n| n.as_str()).zip(self.results.iter())
+    }
+}
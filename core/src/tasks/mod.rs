@@ -1 +1,4 @@
+pub mod graph;
+pub mod progress;
+pub mod retry;
 pub mod tasks;
\ No newline at end of file
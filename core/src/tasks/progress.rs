@@ -0,0 +1,108 @@
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+
+/// Progress of a task, made of weighted child scopes for the steps of the task.
+/// Clones share the same scope, so a task reports into the one it was given and
+/// whoever holds the parent reads the aggregate.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    scope: Arc<Mutex<Scope>>,
+}
+
+#[derive(Debug)]
+struct Scope {
+    name: String,
+    /// Fraction reported by the task itself, used while the scope has no children
+    fraction: f64,
+    finished: bool,
+    /// Weight the children add up to once all of them exist
+    total: Option<f64>,
+    children: Vec<(f64, Progress)>,
+}
+
+impl Progress {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            scope: Arc::new(Mutex::new(Scope {
+                name: name.into(),
+                fraction: 0.0,
+                finished: false,
+                total: None,
+                children: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.scope.lock().unwrap().name.clone()
+    }
+
+    /// Reports the fraction of the task done, between 0 and 1
+    pub fn set(&self, fraction: f64) {
+        self.scope.lock().unwrap().fraction = fraction.clamp(0.0, 1.0);
+    }
+
+    /// Declares the weight of all the children up front, so the aggregate doesn't jump
+    /// when a child is created late. Defaults to the weight of the children created so far.
+    pub fn set_total(&self, weight: f64) {
+        self.scope.lock().unwrap().total = Some(weight.max(0.0));
+    }
+
+    /// Scope for a step standing for `weight` of this one
+    pub fn child(&self, name: impl Into<String>, weight: f64) -> Progress {
+        let child = Progress::new(name);
+        self.scope.lock().unwrap().children.push((weight.max(0.0), child.clone()));
+        child
+    }
+
+    /// Marks the whole scope done, whatever its children reported
+    pub fn finish(&self) {
+        let mut scope = self.scope.lock().unwrap();
+        scope.fraction = 1.0;
+        scope.finished = true;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.scope.lock().unwrap().finished
+    }
+
+    /// Fraction done, the weighted mean of the children when there are any
+    pub fn fraction(&self) -> f64 {
+        let scope = self.scope.lock().unwrap();
+        if scope.finished || scope.children.is_empty() {
+            return scope.fraction;
+        }
+        let weights: f64 = scope.children.iter().map(|(w, _)| w).sum();
+        let total = scope.total.unwrap_or(weights).max(weights);
+        if total <= 0.0 {
+            return scope.fraction;
+        }
+        let done: f64 = scope.children.iter().map(|(w, c)| w * c.fraction()).sum();
+        (done / total).min(1.0)
+    }
+
+    /// Names from this scope down to the step being worked on
+    pub fn current_step(&self) -> Vec<String> {
+        let (name, current) = {
+            let scope = self.scope.lock().unwrap();
+            let current = if scope.finished {
+                None
+            } else {
+                scope.children.iter().map(|(_, c)| c.clone()).find(|c| !c.is_finished())
+            };
+            (scope.name.clone(), current)
+        };
+        let mut steps = vec![name];
+        if let Some(current) = current {
+            steps.extend(current.current_step());
+        }
+        steps
+    }
+}
+
+/// e.g. `42% install 1.21 > assets`
+impl Display for Progress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.0}% {}", self.fraction() * 100.0, self.current_step().join(" > "))
+    }
+}
@@ -0,0 +1,205 @@
+use crate::tasks::progress::Progress;
+use crate::tasks::tasks::{CancellationToken, Task, TaskResult};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+// +============================+
+//          RetryPolicy
+// +============================+
+
+/// How many times a `Retry` runs its task and how long it waits between attempts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    /// Fraction of each wait drawn at random, so the retries of many tasks don't line up
+    jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attempts in total, the first one included
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait after the first failure, it is multiplied after each one up to `max`
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// 0 waits exactly the backoff, 1 anything between none and the backoff
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Wait after the failure of attempt number `attempt`, counting from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let backoff = (self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_backoff.as_secs_f64());
+        Duration::from_secs_f64(backoff * (1.0 - self.jitter * random_fraction()))
+    }
+}
+
+/// Number in `[0, 1)`, every `RandomState` has its own random keys
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+    hasher.write_u32(nanos);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+// +============================+
+//             Retry
+// +============================+
+
+/// A failed attempt about to be retried
+#[derive(Debug, Clone)]
+pub struct RetryAttempt {
+    /// Number of the attempt that failed, counting from 1
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub error: String,
+    /// Wait before the next attempt
+    pub delay: Duration,
+}
+
+type RetryPredicate<S> = Box<dyn Fn(&S, &str) -> bool + Send + Sync>;
+type RetryCallback = Box<dyn Fn(&RetryAttempt) + Send + Sync>;
+
+/// Runs a task again after each failure, following a `RetryPolicy`. Successes and aborts
+/// are returned right away, so are the failures the `retry_if` predicate refuses.
+pub struct Retry<S> {
+    task: S,
+    policy: RetryPolicy,
+    retryable: RetryPredicate<S>,
+    on_retry: Option<RetryCallback>,
+    cancel: CancellationToken,
+    attempts: u32,
+}
+
+impl<S> Retry<S> {
+    pub fn new(task: S, policy: RetryPolicy) -> Self {
+        Self {
+            task,
+            policy,
+            retryable: Box::new(|_, _| true),
+            on_retry: None,
+            cancel: CancellationToken::new(),
+            attempts: 0,
+        }
+    }
+
+    /// Only the failures passing `predicate` are retried, all of them by default. It gets the
+    /// task that failed, which can keep a typed error, and the message of the failure.
+    pub fn retry_if(mut self, predicate: impl Fn(&S, &str) -> bool + Send + Sync + 'static) -> Self {
+        self.retryable = Box::new(predicate);
+        self
+    }
+
+    /// Called before waiting for each retry, to report it
+    pub fn on_retry(mut self, callback: impl Fn(&RetryAttempt) + Send + Sync + 'static) -> Self {
+        self.on_retry = Some(Box::new(callback));
+        self
+    }
+
+    /// Cancelling the token during a wait returns `TaskResult::ABORTED`
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Attempts made by the last run
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.task
+    }
+
+    pub fn into_inner(self) -> S {
+        self.task
+    }
+}
+
+impl<T, S: Task<T>> Task<T> for Retry<S> {
+    async fn execute(&mut self) -> TaskResult<T> {
+        self.attempts = 0;
+        loop {
+            self.attempts += 1;
+            let error = match self.task.execute().await {
+                TaskResult::FAILURE(error) => error,
+                result => return result,
+            };
+            if self.attempts >= self.policy.attempts() || !(self.retryable)(&self.task, &error) {
+                return TaskResult::FAILURE(error);
+            }
+
+            let delay = self.policy.delay(self.attempts);
+            log::debug!("Attempt {}/{} failed: {}. Retrying in {:?}", self.attempts, self.policy.attempts(), error, delay);
+            if let Some(on_retry) = &self.on_retry {
+                on_retry(&RetryAttempt {
+                    attempt: self.attempts,
+                    max_attempts: self.policy.attempts(),
+                    error,
+                    delay,
+                });
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = self.cancel.cancelled() => return TaskResult::ABORTED,
+            }
+        }
+    }
+
+    fn is_abortable(&self) -> bool {
+        true
+    }
+
+    fn abort(&mut self) -> bool {
+        self.cancel.cancel();
+        self.task.abort();
+        true
+    }
+
+    fn weight(&self) -> f64 {
+        self.task.weight()
+    }
+
+    fn attach_progress(&mut self, progress: Progress) {
+        self.task.attach_progress(progress);
+    }
+}
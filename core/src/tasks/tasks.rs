@@ -1,10 +1,19 @@
+use crate::tasks::progress::Progress;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
+pub use tokio_util::sync::CancellationToken;
 
 pub trait Task<T>: Sync + Send {
     fn execute(&mut self) -> impl Future<Output = TaskResult<T>> + Send;
     fn is_abortable(&self) -> bool { false } 
     fn abort(&mut self) -> bool { false }
+    /// Share of its group the task stands for, e.g. the size of a file
+    fn weight(&self) -> f64 { 1.0 }
+    /// Scope the task reports its progress to, groups give one to each of their tasks
+    fn attach_progress(&mut self, _progress: Progress) {}
 }
 
 pub enum TaskResult<R> {
@@ -32,12 +41,112 @@ impl<T, E: ToString> From<Result<T, E>> for TaskResult<T> {
     }
 }
 
-pub struct SequentialTask<S: Task<()>> {
+/// Task running an async closure, for steps that don't need a type of their own
+pub struct FnTask<F> {
+    f: F,
+}
+
+impl<F> FnTask<F> {
+    pub fn new(f: F) -> Self {
+        Self { f }
+    }
+}
+
+impl<T, F, Fut> Task<T> for FnTask<F>
+where
+    F: FnMut() -> Fut + Send + Sync,
+    Fut: Future<Output = TaskResult<T>> + Send,
+{
+    fn execute(&mut self) -> impl Future<Output = TaskResult<T>> + Send {
+        (self.f)()
+    }
+}
+
+/// What a group of tasks does with the rest when one of them fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailurePolicy {
+    /// The remaining tasks are aborted on the first failure
+    FailFast,
+    /// Every task runs whatever the others did
+    #[default]
+    ContinueOnError,
+}
+
+/// Outcome of every task of a `SequentialTask` or `ConcurrentTask`, in the order they were added
+pub struct TaskResults<T> {
+    results: Vec<TaskResult<T>>,
+}
+
+impl<T> TaskResults<T> {
+    pub fn get(&self, index: usize) -> Option<&TaskResult<T>> {
+        self.results.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    pub fn successes(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.results.iter().enumerate().filter_map(|(i, r)| match r {
+            TaskResult::SUCCESS(v) => Some((i, v)),
+            _ => None,
+        })
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &String)> {
+        self.results.iter().enumerate().filter_map(|(i, r)| match r {
+            TaskResult::FAILURE(e) => Some((i, e)),
+            _ => None,
+        })
+    }
+
+    /// Tasks cancelled while running or never started
+    pub fn aborted(&self) -> impl Iterator<Item = usize> {
+        self.results.iter().enumerate().filter_map(|(i, r)| matches!(r, TaskResult::ABORTED).then_some(i))
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.results.iter().all(|r| matches!(r, TaskResult::SUCCESS(_)))
+    }
+
+    pub fn into_results(self) -> Vec<TaskResult<T>> {
+        self.results
+    }
+}
+
+impl<T> Display for TaskResults<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} tasks: {} succeeded, {} failed, {} aborted",
+            self.len(),
+            self.successes().count(),
+            self.failures().count(),
+            self.aborted().count()
+        )
+    }
+}
+
+pub struct SequentialTask<S: Task<T>, T = ()> {
     tasks:  Vec<S>,
+    cancel: CancellationToken,
+    policy: FailurePolicy,
+    progress: Option<Progress>,
+    _output: PhantomData<fn() -> T>,
 }
-impl<S: Task<()>> SequentialTask<S> {
+impl<S: Task<T>, T> SequentialTask<S, T> {
     pub fn new(tasks: Vec<S>) -> Self {
-        Self { tasks }
+        Self {
+            tasks,
+            cancel: CancellationToken::new(),
+            policy: FailurePolicy::default(),
+            progress: None,
+            _output: PhantomData,
+        }
     }
 
     pub fn default() -> Self {
@@ -49,22 +158,77 @@ impl<S: Task<()>> SequentialTask<S> {
         self
     }
 
-    pub async fn run(&mut self) -> TaskResult<()> {
-        for task in &mut self.tasks {
-            task.execute().await;
+    /// Stops running the remaining tasks once the token is cancelled
+    pub fn cancellation(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancel = token;
+        self
+    }
+
+    pub fn policy(&mut self, policy: FailurePolicy) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Reports into `progress`, one child scope per task weighted by `Task::weight`
+    pub fn progress(&mut self, progress: Progress) -> &mut Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Runs the tasks one after the other, the ones left after an abort or a
+    /// fail-fast failure are reported as aborted
+    pub async fn run(&mut self) -> TaskResults<T> {
+        let mut results = Vec::with_capacity(self.tasks.len());
+        let mut stopped = false;
+        let count = self.tasks.len();
+        if let Some(progress) = &self.progress {
+            progress.set_total(self.tasks.iter().map(|t| t.weight()).sum());
+        }
+        for (index, task) in self.tasks.iter_mut().enumerate() {
+            if stopped || self.cancel.is_cancelled() {
+                results.push(TaskResult::ABORTED);
+                continue;
+            }
+            let step = self.progress.as_ref().map(|p| p.child(format!("{}/{}", index + 1, count), task.weight()));
+            if let Some(step) = &step {
+                task.attach_progress(step.clone());
+            }
+            let result = task.execute().await;
+            if let Some(step) = step {
+                step.finish();
+            }
+            stopped = match &result {
+                TaskResult::SUCCESS(_) => false,
+                TaskResult::FAILURE(_) => self.policy == FailurePolicy::FailFast,
+                TaskResult::ABORTED => true,
+            };
+            results.push(result);
         }
-        TaskResult::SUCCESS(())
+        let results = TaskResults { results };
+        log::debug!("sequential tasks finished, {}", results);
+        results
     }
 }
 
-pub struct ConcurrentTask<C: Task<()>> {
+pub struct ConcurrentTask<C: Task<T>, T = ()> {
     tasks: Vec<C>,
     max_concurrent_tasks: usize,
+    cancel: CancellationToken,
+    policy: FailurePolicy,
+    progress: Option<Progress>,
+    _output: PhantomData<fn() -> T>,
 }
 
-impl<C: Task<()> + 'static> ConcurrentTask<C> {
+impl<C: Task<T> + 'static, T: Send + 'static> ConcurrentTask<C, T> {
     pub fn new(tasks: Vec<C>, max_concurrent_tasks: usize) -> Self {
-        Self { tasks, max_concurrent_tasks }
+        Self {
+            tasks,
+            max_concurrent_tasks,
+            cancel: CancellationToken::new(),
+            policy: FailurePolicy::default(),
+            progress: None,
+            _output: PhantomData,
+        }
     }
 
     pub fn default() -> Self {
@@ -76,28 +240,104 @@ impl<C: Task<()> + 'static> ConcurrentTask<C> {
         self
     }
 
-    pub async fn run(&mut self) -> TaskResult<()> {
-        let semaphore = Arc::new(Semaphore::new(self.max_concurrent_tasks));
+    /// Stops spawning new tasks once the token is cancelled, running tasks
+    /// are expected to watch the same token and return `TaskResult::ABORTED`
+    pub fn cancellation(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancel = token;
+        self
+    }
+
+    /// With `FailurePolicy::FailFast` the first failure stops the running tasks
+    /// where they are and the pending ones never start
+    pub fn policy(&mut self, policy: FailurePolicy) -> &mut Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Reports into `progress`, one child scope per task weighted by `Task::weight`
+    pub fn progress(&mut self, progress: Progress) -> &mut Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub async fn run(&mut self) -> TaskResults<T> {
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrent_tasks.max(1)));
+        let failed = CancellationToken::new();
         let mut set = tokio::task::JoinSet::new();
+        let mut running: HashMap<tokio::task::Id, usize> = HashMap::new();
+        let mut results: Vec<TaskResult<T>> = (0..self.tasks.len()).map(|_| TaskResult::ABORTED).collect();
 
         log::debug!("Tareas recibidas {:?}", self.tasks.len());
-        for mut task in self.tasks.drain(..) {
-            let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let count = self.tasks.len();
+        if let Some(progress) = &self.progress {
+            progress.set_total(self.tasks.iter().map(|t| t.weight()).sum());
+        }
+        for (index, mut task) in self.tasks.drain(..).enumerate() {
+            // finished tasks are collected while waiting, a failure has to stop the spawning
+            let permit = loop {
+                tokio::select! {
+                    permit = semaphore.clone().acquire_owned() => break Some(permit.unwrap()),
+                    _ = self.cancel.cancelled() => break None,
+                    _ = failed.cancelled() => break None,
+                    Some(joined) = set.join_next_with_id() => {
+                        Self::collect(joined, &running, &mut results, &failed, self.policy);
+                    }
+                }
+            };
+            let Some(permit) = permit else { break };
 
-            set.spawn(async move {
+            let step = self.progress.as_ref().map(|p| p.child(format!("{}/{}", index + 1, count), task.weight()));
+            if let Some(step) = &step {
+                task.attach_progress(step.clone());
+            }
+            let failed = failed.clone();
+            let handle = set.spawn(async move {
                 let _permit = permit;
-                task.execute().await;
+                let result = tokio::select! {
+                    biased;
+                    result = task.execute() => result,
+                    _ = failed.cancelled() => TaskResult::ABORTED,
+                };
+                if let Some(step) = step {
+                    step.finish();
+                }
+                result
             });
+            running.insert(handle.id(), index);
+        }
+
+        while let Some(joined) = set.join_next_with_id().await {
+            Self::collect(joined, &running, &mut results, &failed, self.policy);
         }
+        let results = TaskResults { results };
+        if self.cancel.is_cancelled() {
+            log::debug!("tasks aborted, {}", results);
+        } else {
+            log::debug!("tasks finished, {}", results);
+        }
+        results
+    }
 
-        while let Some(result) = set.join_next().await {
-            match result {
-                Ok(n) => log::debug!("task result: {:?}", n),
-                Err(err) => log::error!("task result: {}", err),
+    fn collect(
+        joined: Result<(tokio::task::Id, TaskResult<T>), tokio::task::JoinError>,
+        running: &HashMap<tokio::task::Id, usize>,
+        results: &mut [TaskResult<T>],
+        failed: &CancellationToken,
+        policy: FailurePolicy,
+    ) {
+        let (index, result) = match joined {
+            Ok((id, result)) => (running[&id], result),
+            Err(e) => {
+                log::error!("task result: {}", e);
+                (running[&e.id()], TaskResult::FAILURE(e.to_string()))
+            }
+        };
+        if let TaskResult::FAILURE(e) = &result {
+            log::debug!("task result: {}", e);
+            if policy == FailurePolicy::FailFast {
+                failed.cancel();
             }
         }
-        log::debug!("tasks finished");
-        TaskResult::SUCCESS(())
+        results[index] = result;
     }
 }
-
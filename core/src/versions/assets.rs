@@ -0,0 +1,53 @@
+use crate::versions::version_json::AssetsJson;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Directory the game reads its assets from, `${game_assets}` in the arguments: the
+/// object store for the current indexes, a tree of named files for the old ones
+pub fn game_assets_path(assets_json: &AssetsJson, minecraft_path: &str, index_id: &str, game_dir: &str) -> PathBuf {
+    if assets_json.map_to_resources {
+        Path::new(game_dir).join("resources")
+    } else if assets_json.is_virtual {
+        Path::new(minecraft_path).join("assets").join("virtual").join(index_id)
+    } else {
+        Path::new(minecraft_path).join("assets")
+    }
+}
+
+/// Lays out the assets of a virtual or `map_to_resources` index by name, linking the files
+/// of the object store or copying them where links are not possible. Files already in
+/// place with the right size are kept. Returns the number of files laid out.
+pub fn reconcile_assets(assets_json: &AssetsJson, minecraft_path: &str, index_id: &str, game_dir: &str) -> io::Result<usize> {
+    if !assets_json.is_virtual && !assets_json.map_to_resources {
+        return Ok(0);
+    }
+    let objects = Path::new(minecraft_path).join("assets").join("objects");
+    let target = game_assets_path(assets_json, minecraft_path, index_id, game_dir);
+    let mut placed = 0;
+    for (name, asset) in &assets_json.objects {
+        // names come from the index, `../` in one would write outside of `target`
+        let name = Path::new(name);
+        if !name.components().all(|c| matches!(c, Component::Normal(_))) {
+            log::warn!("Skipping asset {}, it points outside of {}", name.display(), target.display());
+            continue;
+        }
+        let destination = target.join(name);
+        if fs::metadata(&destination).is_ok_and(|m| m.len() == asset.size) {
+            continue;
+        }
+        let source = objects.join(asset.object_path());
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if destination.exists() {
+            fs::remove_file(&destination)?;
+        }
+        if fs::hard_link(&source, &destination).is_err() {
+            fs::copy(&source, &destination)?;
+        }
+        placed += 1;
+    }
+    log::info!("{} assets of {} laid out in {}", placed, index_id, target.display());
+    Ok(placed)
+}
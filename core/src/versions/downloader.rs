@@ -1,13 +1,31 @@
 use std::{io};
 use std::path::Path;
-use std::sync::Arc;
 use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
+use tokio::sync::broadcast::error::RecvError;
 use crate::versions::version_json::{AssetsJson, Library, VersionJson, VersionType};
-use crate::downloader::downloader::{Downloader, DownloaderTracking, DownloadState, FileData};
+use crate::downloader::downloader::{Downloader, DownloadPhase, DownloadPriority, DownloadState, FileData};
+use crate::downloader::events::{DownloadEvent, EventReceiver, EventSender};
+use crate::downloader::limiter::BandwidthLimiter;
+use crate::downloader::report::DownloadReport;
 use crate::launcher::launcher_config::LauncherConfig;
+use crate::tasks::graph::TaskGraph;
+use crate::versions::journal::InstallJournal;
+use crate::tasks::progress::Progress;
+use crate::tasks::tasks::{CancellationToken, Task, TaskResult};
+use crate::versions::assets;
+use crate::versions::natives;
+use crate::versions::rules::LaunchContext;
 use crate::versions::version::Version;
 
+/// Host of the asset objects, mirrors for it are set in `LauncherConfig::mirrors`
+const RESOURCES_URL: &str = "https://resources.download.minecraft.net";
+
+/// Age after which a leftover `.part` file is discarded instead of resumed
+const STALE_PART_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
 pub struct VersionDownloadTask<'a> {
     version: &'a str,
     json: Option<VersionJson>,
@@ -17,150 +35,236 @@ impl VersionDownloadTask<'_> {
     
     pub async fn download_version(
         version: Box<dyn Version + 'static>,
-        progress: Arc<Mutex<DownloaderTracking>>,
-    ) -> io::Result<()>
+        events: EventSender,
+        cancel: CancellationToken,
+        progress: Progress,
+    ) -> io::Result<DownloadReport>
     {
         log::info!("Matching version type: {:?}", version.version_type());
         match version.version_type() {
             VersionType::RELEASE
             | VersionType::SNAPSHOT
             | VersionType::OldBeta
-            | VersionType::OldAlpha => Self::download_standard(version, progress).await,
+            | VersionType::OldAlpha => Self::download_standard(version, events, cancel, progress).await,
         }
     }
 
-    async fn download_standard(version: Box<dyn Version + 'static>, progress: Arc<Mutex<DownloaderTracking>>) -> io::Result<()> {
+    async fn download_standard(
+        version: Box<dyn Version + 'static>,
+        events: EventSender,
+        cancel: CancellationToken,
+        progress: Progress,
+    ) -> io::Result<DownloadReport> {
         // Initialize variables
         let config = LauncherConfig::import_config();
-        let downloader = Downloader::builder()
-            .concurret_downloads(64)
-            .retries(5)
-            .progress(progress.clone())
-            .build()?;
         log::info!("Download_standard version: {:?}", version);
 
-        // ensure intial files are downloaded
-        let vc = version.clone();
-        progress.lock().await.set_state(DownloadState::DownloadingInitials);
-        Self::download_initial_files(&vc, downloader).await.expect("Unable to asecure initial files");
-        
-        // version json local
-        let minecraft_path = config.minecraft_path.clone();
-        let version_json = VersionJson::get_from_local(&minecraft_path, &version.name()).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        
-        //  Calculate total of files to download and set value to progress
-        let assets_json = version_json.get_assets_json();
-        let total_assets = assets_json.clone().objects.len();
-        let total_libraries = version_json.get_libraries().len();
-        
-        // calculate total of files
-        let total_objects: usize = total_libraries + total_assets;
-        log::info!("assets: {total_assets}, lib: {total_libraries}, t: {total_objects}");
-        
-        //  Spawn threads
-        progress.lock().await.set_progress((0usize, total_objects));
-        let mut total_files = Self::libraries_files(
-            version_json.get_libraries(),
-            Path::new(&minecraft_path)
-        )?;
-        let mut assets_files = Self::assets_files(
-            assets_json,
-            Path::new(&minecraft_path)
-        )?;
-        total_files.append(&mut assets_files);
-
-        let downloader_concurrent = Downloader::builder()
+        // partial files from interrupted runs are resumed, but not forever
+        let removed = Downloader::clean_stale_parts(Path::new(&config.minecraft_path), STALE_PART_AGE);
+        if removed > 0 {
+            log::info!("Removed {removed} stale partial downloads");
+        }
+        if let Err(e) = fs::create_dir_all(&config.minecraft_path) {
+            log::warn!("Failed to create {}: {}", config.minecraft_path, e);
+        }
+
+        // what completes is written down, a crash or a restart resumes with the missing files
+        let journal = InstallJournal::open(&config.minecraft_path, version.as_ref())?;
+        let install = InstallSteps {
+            minecraft_path: config.minecraft_path.clone(),
+            version_name: version.name(),
+            json_url: version.json_url(),
+            downloader: Arc::new(Self::downloader(&config, &events, &cancel)?),
+            report: Arc::new(Mutex::new(DownloadReport::new())),
+            journal: Arc::new(Mutex::new(journal)),
+            events: events.clone(),
+        };
+        let stop_recording = CancellationToken::new();
+        let recorder = tokio::spawn(InstallSteps::record_completed(
+            install.journal.clone(),
+            events.subscribe(),
+            stop_recording.clone(),
+        ));
+
+        // each step starts once the files it reads are on disk
+        // weighted by their usual share of the time of an install
+        let mut graph: TaskGraph<()> = TaskGraph::new(4);
+        graph.cancellation(cancel.clone()).progress(progress);
+        let version_json = graph.add_task("version json", install.step(1.0, InstallSteps::version_json), &[]);
+        let asset_index = graph.add_task("asset index", install.step(1.0, InstallSteps::asset_index), &[version_json]);
+        let disk_space = graph.add_task("disk space", install.step(1.0, InstallSteps::disk_space), &[asset_index]);
+        let libraries = graph.add_task("libraries", install.step(34.0, InstallSteps::libraries), &[disk_space]);
+        let natives = graph.add_task("natives", install.step(1.0, InstallSteps::natives), &[libraries]);
+        let assets = graph.add_task("assets", install.step(54.0, InstallSteps::assets), &[disk_space]);
+        let asset_layout = graph.add_task("asset layout", install.step(1.0, InstallSteps::asset_layout), &[assets]);
+        graph.add_task("verify", install.step(7.0, InstallSteps::verify), &[natives, asset_layout]);
+
+        let _ = events.send(DownloadEvent::PhaseChanged(DownloadState::DownloadingInitials));
+        let results = graph.run().await;
+        drop(graph);
+        stop_recording.cancel();
+        if let Err(e) = recorder.await {
+            log::warn!("Install journal recorder failed: {e}");
+        }
+        let mut report = std::mem::take(&mut *install.report.lock().await);
+        // steps aborted before they downloaded anything leave no trace in the report
+        if cancel.is_cancelled() {
+            report.set_aborted();
+        }
+
+        // refuse to start instead of filling the disk halfway through the assets
+        if let TaskResult::FAILURE(e) = results.get(disk_space) {
+            log::error!("Install of {} refused: {}", install.version_name, e);
+            Self::discard_failed_install(&install.minecraft_path, &install.version_name);
+            let _ = events.send(DownloadEvent::PhaseChanged(DownloadState::Aborted));
+            return Err(io::Error::new(io::ErrorKind::StorageFull, e.clone()));
+        }
+
+        // the client and its libraries are enough to play, the assets can be fetched later
+        if !results.succeeded(natives) {
+            Self::discard_failed_install(&install.minecraft_path, &install.version_name);
+        } else if !results.is_success() {
+            log::warn!("{} is launchable, assets incomplete", install.version_name);
+        }
+        if results.is_success() {
+            let InstallSteps { journal, version_name, .. } = install;
+            match Arc::try_unwrap(journal) {
+                Ok(journal) => {
+                    if let Err(e) = journal.into_inner().finish() {
+                        log::warn!("Failed to remove the install journal of {}: {}", version_name, e);
+                    }
+                }
+                Err(_) => log::warn!("Install journal of {} still in use, left in place", version_name),
+            }
+        }
+        let _ = events.send(DownloadEvent::PhaseChanged(Self::final_state(&report)));
+        Ok(report)
+    }
+
+    fn downloader(config: &LauncherConfig, events: &EventSender, cancel: &CancellationToken) -> io::Result<Downloader> {
+        let mut builder = Downloader::builder();
+        builder
             .concurret_downloads(64)
+            .adaptive_concurrency(true)
+            .max_connections_per_host(config.max_connections_per_host)
             .retries(5)
-            .progress(progress.clone())
-            .build()?;
+            .events(events.clone())
+            .cancellation(cancel.clone())
+            .mirrors(config.mirrors())
+            .limiter(BandwidthLimiter::global());
+        if let Some(transport) = config.local_transport() {
+            builder.transport(transport);
+        }
+        if let Some(stall_timeout) = config.stall_timeout {
+            builder.stall_timeout(Duration::from_secs(stall_timeout));
+        }
+        builder.build()
+    }
 
-        // Spawn // TODO: Es necesarop tenerlo en un hilo?
-        tokio::spawn(async move {
-            downloader_concurrent.download_files_concurrently(total_files).await
-        });
-        
+    fn final_state(report: &DownloadReport) -> DownloadState {
+        if report.is_aborted() {
+            DownloadState::Aborted
+        } else {
+            DownloadState::Finished
+        }
+    }
 
-        // Wait until finish
-        loop {
-            let p = progress.lock().await;
-            log::debug!("ACTUAL PROGRESS: {:?}  UNITS: {:?}", p.progress(), p.units().len());
-            match (p.finished(), p.state()) { 
-                (true, _) | (_, DownloadState::Finished) => {break}
-                _ => {}
-            }
+    /// Removes the version json so a failed install is not detected as installed.
+    fn discard_failed_install(minecraft_path: &str, version_name: &str) {
+        let json = Path::new(minecraft_path)
+            .join("versions")
+            .join(version_name)
+            .join(format!("{}.json", version_name));
+        log::error!("Install of {} did not complete, removing {}", version_name, json.display());
+        if let Err(e) = fs::remove_file(&json) {
+            log::warn!("Failed to remove {}: {}", json.display(), e);
         }
-        Ok(())
     }
 
-    async fn download_initial_files(version: &Box<dyn Version + 'static>, mut downloader: Downloader) -> io::Result<()> {
-        let LauncherConfig { minecraft_path, .. } = LauncherConfig::import_config();
-        match fs::create_dir_all(minecraft_path.clone()) {
-            Ok(e) => log::info!("Directory created {:?}", e),
-            _ => {}
+    /// Refuses the install when the files left to download don't fit on the disk of `minecraft_path`
+    fn check_disk_space(minecraft_path: &Path, files: &[FileData]) -> io::Result<()> {
+        let required = Downloader::required_space(files);
+        let available = fs4::available_space(minecraft_path)?;
+        log::info!("Install needs {} bytes, {} bytes available", required, available);
+        if required > available {
+            return Err(io::Error::new(
+                io::ErrorKind::StorageFull,
+                format!(
+                    "not enough disk space on {}: {} MB needed, {} MB available",
+                    minecraft_path.display(),
+                    required.div_ceil(1024 * 1024),
+                    available / (1024 * 1024)
+                ),
+            ));
         }
+        Ok(())
+    }
 
-        let version_name = version.name();
-        let version_json = VersionJson::get_from_local(&minecraft_path, &version_name)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        let assets_index = version_json.get_asset_index();
-        let files = vec![
-            FileData::new( // Version json
-                format!("{}/versions/{}/{}.json", &minecraft_path, &version_name, &version_name).to_string(),
-                version.json_url(),
-                None
-            ),
-            FileData::new( // Asset json
-                Path::new(&minecraft_path)
-                    .join("assets")
-                    .join("indexes")
-                    .join(format!("{}.json", assets_index.id).as_str())
-                    .to_str().unwrap().to_string(),
-                assets_index.url,
-                None
-            ),
+    fn client_files(version_json: &VersionJson, minecraft_path: &str, version_name: &str) -> Vec<FileData> {
+        let mut files = vec![
             FileData::new( // client
-                Path::new(&minecraft_path.clone())
+                Path::new(minecraft_path)
                     .join("versions")
-                    .join(version_name.as_str())
+                    .join(version_name)
                     .join(format!("{}.jar", version_name).as_str())
                     .to_str().unwrap().to_string(),
                 version_json.get_client_url(),
-                None
-            ),
-            FileData::new(
-                Path::new(&minecraft_path.clone())
+                Some(version_json.get_client_sha1())
+            ).size(version_json.get_client_size()).phase(DownloadPhase::Initials),
+        ];
+        if let Some(mappings) = version_json.get_client_mappings() {
+            files.push(FileData::new(
+                Path::new(minecraft_path)
                     .join("versions")
-                    .join(version_name.as_str())
+                    .join(version_name)
                     .join(format!("{}.txt", version_name).as_str())
                     .to_str().unwrap().to_string(),
-                version_json.get_client_mappings_url(),
-                None
-            )
-        ];
+                mappings.url().to_string(),
+                Some(mappings.sha1().to_string())
+            ).size(mappings.size()).phase(DownloadPhase::Initials));
+        }
+        files
+    }
 
-        downloader.download_files_concurrently(files).await.expect("Failed to download initial files");
-        downloader.clean_progress().await;
-        Ok(())
+    fn asset_index_file(version_json: &VersionJson, minecraft_path: &str) -> FileData {
+        let assets_index = version_json.get_asset_index();
+        FileData::new(
+            Path::new(minecraft_path)
+                .join("assets")
+                .join("indexes")
+                .join(format!("{}.json", assets_index.id).as_str())
+                .to_str().unwrap().to_string(),
+            assets_index.url,
+            Some(assets_index.sha1)
+        ).size(assets_index.size).phase(DownloadPhase::Initials)
     }
 
-    fn libraries_files(libraries: Vec<Library>, minecraft_path: &Path) -> io::Result<Vec<FileData>> {
+    fn libraries_files(libraries: Vec<Library>, minecraft_path: &Path, context: &LaunchContext) -> io::Result<Vec<FileData>> {
         let mut files: Vec<FileData> = Vec::new();
-        libraries.into_iter()
-            //.filter(|lib| {
-            //     ! (lib.is_native() && lib.filter_native_by_os())
-            //})
+        libraries.iter()
             .for_each(
                 |lib| {
-                    let url = lib.get_download_url();
-                    let path = Path::new(minecraft_path)
-                        .join("libraries")
-                        .join(lib.get_path())
-                        .as_path()
-                        .display()
-                        .to_string();
-                    let sha1 = lib.get_sha1();
-                    files.push(FileData::new(path, url, Some(sha1)));
+                    let natives = lib.native_files(context);
+                    for jar in lib.files(context) {
+                        let Some(jar_path) = jar.path() else { continue };
+                        let path = Path::new(minecraft_path)
+                            .join("libraries")
+                            .join(jar_path)
+                            .as_path()
+                            .display()
+                            .to_string();
+                        let priority = if natives.iter().any(|n| n.path() == Some(jar_path)) {
+                            DownloadPriority::Natives
+                        } else {
+                            DownloadPriority::Libraries
+                        };
+                        files.push(
+                            FileData::new(path, jar.url().to_string(), Some(jar.sha1().to_string()))
+                                .size(jar.size())
+                                .phase(DownloadPhase::Libraries)
+                                .priority(priority)
+                        );
+                    }
                 }
             );
         Ok(files)
@@ -170,13 +274,276 @@ impl VersionDownloadTask<'_> {
         let mut files: Vec<FileData> = Vec::new();
         let assets_dir = minecraft_path.join("assets").join("objects");
         assets.objects.into_iter().for_each(|object| {
+            let dir = object.1.object_path();
             let hash = object.1.hash;
-            let dir = format!("{}/{}", &hash[..2], hash);
-            let url = format!("https://resources.download.minecraft.net/{}", dir);
+            let size = object.1.size;
+            let url = format!("{}/{}", RESOURCES_URL, dir);
             let file_path = assets_dir.join(dir.clone());
+            // sounds and music are the bulk of the assets and the least needed
+            let priority = if object.0.ends_with(".ogg") {
+                DownloadPriority::Sounds
+            } else {
+                DownloadPriority::Assets
+            };
 
-            files.push(FileData::new(file_path.to_str().unwrap().to_string(), url, None));
+            // asset objects are addressed by their own sha1
+            files.push(
+                FileData::new(file_path.to_str().unwrap().to_string(), url, Some(hash))
+                    .size(size)
+                    .phase(DownloadPhase::Assets)
+                    .priority(priority)
+            );
         });
         Ok(files)
     }
-}
\ No newline at end of file
+}
+
+// +============================+
+//         InstallSteps
+// +============================+
+
+/// What the steps of an install share. Every step reads the jsons written by the steps
+/// it depends on back from disk.
+#[derive(Clone)]
+struct InstallSteps {
+    minecraft_path: String,
+    version_name: String,
+    json_url: String,
+    downloader: Arc<Downloader>,
+    report: Arc<Mutex<DownloadReport>>,
+    journal: Arc<Mutex<InstallJournal>>,
+    events: EventSender,
+}
+
+impl InstallSteps {
+    /// Task running `run` on a copy of the shared state, `weight` is its share of the install
+    fn step<F, Fut>(&self, weight: f64, run: F) -> InstallStep<F>
+    where
+        F: Fn(InstallSteps, Progress) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = TaskResult<()>> + Send + 'static,
+    {
+        InstallStep { install: self.clone(), run, weight, progress: Progress::new("") }
+    }
+
+    fn version_json_file(&self) -> FileData {
+        FileData::new(
+            format!("{}/versions/{}/{}.json", &self.minecraft_path, &self.version_name, &self.version_name),
+            self.json_url.clone(),
+            None
+        ).phase(DownloadPhase::Initials)
+    }
+
+    fn read_version_json(&self) -> Result<VersionJson, String> {
+        VersionJson::get_from_local(&self.minecraft_path, &self.version_name)
+    }
+
+    /// Install files of the client and its libraries, and of the assets
+    fn install_files(&self) -> io::Result<(Vec<FileData>, Vec<FileData>)> {
+        let version_json = self.read_version_json().map_err(io::Error::other)?;
+        let minecraft_path = Path::new(&self.minecraft_path);
+        let mut libraries = VersionDownloadTask::client_files(&version_json, &self.minecraft_path, &self.version_name);
+        let context = LaunchContext::current();
+        libraries.append(&mut VersionDownloadTask::libraries_files(version_json.get_libraries_for(&context), minecraft_path, &context)?);
+        let assets = VersionDownloadTask::assets_files(version_json.get_assets_json().map_err(io::Error::other)?, minecraft_path)?;
+        log::info!("lib: {}, assets: {}", libraries.len(), assets.len());
+        Ok((libraries, assets))
+    }
+
+    /// Writes the files the downloader finishes to the journal until `stop` is cancelled
+    async fn record_completed(journal: Arc<Mutex<InstallJournal>>, mut receiver: EventReceiver, stop: CancellationToken) {
+        loop {
+            // the events sent before the stop are still taken
+            let event = tokio::select! {
+                biased;
+                event = receiver.recv() => event,
+                _ = stop.cancelled() => break,
+            };
+            match event {
+                Ok(DownloadEvent::FileFinished { path, .. }) => {
+                    if let Err(e) = journal.lock().await.complete(&path) {
+                        log::warn!("Failed to record {} in the install journal: {}", path, e);
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => log::warn!("{n} events missed by the install journal"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+
+    /// Drops the files a previous run completed and that are still on disk
+    async fn missing(&self, files: Vec<FileData>) -> Vec<FileData> {
+        let journal = self.journal.lock().await;
+        let install = journal.install();
+        let total = files.len();
+        let missing: Vec<FileData> = files
+            .into_iter()
+            .filter(|file| {
+                let size = fs::metadata(file.path()).map(|m| m.len()).ok();
+                let intact = size.is_some() && file.expected_size().is_none_or(|s| Some(s) == size);
+                !(install.is_completed(file.path()) && intact)
+            })
+            .collect();
+        if missing.len() < total {
+            log::info!("{} of {} files already completed", total - missing.len(), total);
+        }
+        missing
+    }
+
+    /// Downloads `files` into the shared report, the step fails if any of them did
+    async fn download(&self, files: Vec<FileData>, progress: &Progress) -> TaskResult<()> {
+        let report = match self.downloader.download_files_with_progress(files, progress).await {
+            Ok(report) => report,
+            Err(e) => return TaskResult::FAILURE(e.to_string()),
+        };
+        let result = if report.is_aborted() {
+            TaskResult::ABORTED
+        } else if !report.is_success() {
+            TaskResult::FAILURE(format!("{} files failed", report.failed().len()))
+        } else {
+            TaskResult::SUCCESS(())
+        };
+        self.report.lock().await.merge(report);
+        result
+    }
+
+    async fn version_json(self, progress: Progress) -> TaskResult<()> {
+        self.download(vec![self.version_json_file()], &progress).await
+    }
+
+    async fn asset_index(self, progress: Progress) -> TaskResult<()> {
+        match self.read_version_json() {
+            Ok(version_json) => {
+                let file = VersionDownloadTask::asset_index_file(&version_json, &self.minecraft_path);
+                self.download(vec![file], &progress).await
+            }
+            Err(e) => TaskResult::FAILURE(e),
+        }
+    }
+
+    async fn disk_space(self, _progress: Progress) -> TaskResult<()> {
+        let result = match self.install_files() {
+            Ok((mut files, mut assets)) => {
+                files.append(&mut assets);
+                match self.journal.lock().await.plan(&files) {
+                    Ok(()) => VersionDownloadTask::check_disk_space(Path::new(&self.minecraft_path), &files),
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        if result.is_ok() {
+            let _ = self.events.send(DownloadEvent::PhaseChanged(DownloadState::Downloading));
+        }
+        result.into()
+    }
+
+    async fn libraries(self, progress: Progress) -> TaskResult<()> {
+        match self.install_files() {
+            Ok((libraries, _)) => {
+                self.download(self.missing(libraries).await, &progress).await
+            }
+            Err(e) => TaskResult::FAILURE(e.to_string()),
+        }
+    }
+
+    /// Extracts the natives jars into `versions/<id>/natives`, after that the game can start
+    async fn natives(self, _progress: Progress) -> TaskResult<()> {
+        let version_json = match self.read_version_json() {
+            Ok(version_json) => version_json,
+            Err(e) => return TaskResult::FAILURE(e),
+        };
+        let (minecraft_path, version_name) = (self.minecraft_path.clone(), self.version_name.clone());
+        let extracted = tokio::task::spawn_blocking(move || {
+            natives::extract_natives(&version_json, &minecraft_path, &version_name, &LaunchContext::current())
+        })
+        .await
+        .map_err(io::Error::other)
+        .and_then(|result| result);
+        if let Err(e) = extracted {
+            return TaskResult::FAILURE(format!("Failed to extract natives: {e}"));
+        }
+        if let Err(e) = self.journal.lock().await.set_launchable() {
+            log::warn!("Failed to record {} as launchable: {}", self.version_name, e);
+        }
+        TaskResult::SUCCESS(())
+    }
+
+    async fn assets(self, progress: Progress) -> TaskResult<()> {
+        match self.install_files() {
+            Ok((_, assets)) => self.download(self.missing(assets).await, &progress).await,
+            Err(e) => TaskResult::FAILURE(e.to_string()),
+        }
+    }
+
+    /// Lays out the assets by name for the indexes of the versions before 1.7.10, in the
+    /// default game directory
+    async fn asset_layout(self, _progress: Progress) -> TaskResult<()> {
+        let version_json = match self.read_version_json() {
+            Ok(version_json) => version_json,
+            Err(e) => return TaskResult::FAILURE(e),
+        };
+        let minecraft_path = self.minecraft_path.clone();
+        let placed = tokio::task::spawn_blocking(move || {
+            let index_id = version_json.get_asset_index().id;
+            let assets_json = version_json.get_assets_json().map_err(io::Error::other)?;
+            assets::reconcile_assets(&assets_json, &minecraft_path, &index_id, &minecraft_path)
+        })
+        .await
+        .map_err(io::Error::other)
+        .and_then(|result| result);
+        match placed {
+            Ok(_) => TaskResult::SUCCESS(()),
+            Err(e) => TaskResult::FAILURE(format!("Failed to lay out the assets: {e}")),
+        }
+    }
+
+    /// Every file the install needs is on disk with its expected size
+    async fn verify(self, progress: Progress) -> TaskResult<()> {
+        let (mut files, mut assets) = match self.install_files() {
+            Ok(files) => files,
+            Err(e) => return TaskResult::FAILURE(e.to_string()),
+        };
+        files.append(&mut assets);
+        let total = files.len().max(1);
+        let missing = files.iter()
+            .enumerate()
+            .filter(|(i, file)| {
+                progress.set(*i as f64 / total as f64);
+                let size = fs::metadata(file.path()).map(|m| m.len()).ok();
+                size.is_none() || file.expected_size().is_some_and(|s| Some(s) != size)
+            })
+            .count();
+        if missing > 0 {
+            return TaskResult::FAILURE(format!("{missing} files missing after install"));
+        }
+        log::info!("{} verified", self.version_name);
+        TaskResult::SUCCESS(())
+    }
+}
+
+/// A step of the install graph, reporting into the scope the graph gives it
+struct InstallStep<F> {
+    install: InstallSteps,
+    run: F,
+    weight: f64,
+    progress: Progress,
+}
+
+impl<F, Fut> Task<()> for InstallStep<F>
+where
+    F: Fn(InstallSteps, Progress) -> Fut + Send + Sync,
+    Fut: Future<Output = TaskResult<()>> + Send,
+{
+    fn execute(&mut self) -> impl Future<Output = TaskResult<()>> + Send {
+        (self.run)(self.install.clone(), self.progress.clone())
+    }
+
+    fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    fn attach_progress(&mut self, progress: Progress) {
+        self.progress = progress;
+    }
+}
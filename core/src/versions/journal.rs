@@ -0,0 +1,248 @@
+use crate::downloader::downloader::FileData;
+use crate::versions::version::Version;
+use crate::versions::version_json::VersionType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the journal inside the directory of the version
+const JOURNAL_FILE: &str = "install.journal";
+
+/// One line of the journal
+#[derive(Serialize, Deserialize, Debug)]
+enum Record {
+    Started { version: String, version_type: VersionType, json_url: String },
+    /// Files of the install, a later plan replaces the previous one
+    Planned { files: Vec<PlannedFile> },
+    Completed { path: String },
+    /// The client and the libraries are in place, the game can start without the rest
+    Launchable,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlannedFile {
+    pub path: String,
+    pub size: Option<u64>,
+}
+
+// +============================+
+//       UnfinishedInstall
+// +============================+
+
+/// What the journal of an install tells about it
+#[derive(Debug, Clone)]
+pub struct UnfinishedInstall {
+    version: String,
+    version_type: VersionType,
+    json_url: String,
+    planned: Vec<PlannedFile>,
+    completed: HashSet<String>,
+    launchable: bool,
+}
+
+impl UnfinishedInstall {
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn version_type(&self) -> VersionType {
+        self.version_type.clone()
+    }
+
+    pub fn json_url(&self) -> &str {
+        &self.json_url
+    }
+
+    /// Files of the install, empty until the version json and asset index were fetched
+    pub fn planned(&self) -> &Vec<PlannedFile> {
+        &self.planned
+    }
+
+    pub fn is_completed(&self, path: &str) -> bool {
+        self.completed.contains(path)
+    }
+
+    /// Planned files that were not completed
+    pub fn missing(&self) -> Vec<&PlannedFile> {
+        self.planned.iter().filter(|f| !self.completed.contains(&f.path)).collect()
+    }
+
+    /// Completed and planned files
+    pub fn files(&self) -> (usize, usize) {
+        (self.planned.len() - self.missing().len(), self.planned.len())
+    }
+
+    pub fn is_launchable(&self) -> bool {
+        self.launchable
+    }
+
+    fn apply(&mut self, record: Record) {
+        match record {
+            Record::Started { version, version_type, json_url } => {
+                self.version = version;
+                self.version_type = version_type;
+                self.json_url = json_url;
+            }
+            Record::Planned { files } => self.planned = files,
+            Record::Completed { path } => {
+                self.completed.insert(path);
+            }
+            Record::Launchable => self.launchable = true,
+        }
+    }
+}
+
+// +============================+
+//         InstallJournal
+// +============================+
+
+/// On-disk record of an install in progress, in `versions/<id>/install.journal` until the
+/// install completes. Records are appended one per line as the install goes, so a crash
+/// loses at most the line being written.
+pub struct InstallJournal {
+    path: PathBuf,
+    file: File,
+    install: UnfinishedInstall,
+}
+
+impl InstallJournal {
+    pub fn path(minecraft_path: &str, version_name: &str) -> PathBuf {
+        Path::new(minecraft_path).join("versions").join(version_name).join(JOURNAL_FILE)
+    }
+
+    pub fn exists(minecraft_path: &str, version_name: &str) -> bool {
+        Self::path(minecraft_path, version_name).exists()
+    }
+
+    /// Continues the journal of an interrupted install of `version`, or starts a new one
+    pub fn open(minecraft_path: &str, version: &dyn Version) -> io::Result<Self> {
+        let path = Self::path(minecraft_path, &version.name());
+        if path.exists() {
+            let install = Self::read(&path)?;
+            let (done, planned) = install.files();
+            log::info!("Resuming install of {}, {}/{} files done", version.name(), done, planned);
+            let mut file = OpenOptions::new().append(true).open(&path)?;
+            // ends a line torn by a crash, the next record would be lost with it
+            if !fs::read(&path)?.ends_with(b"\n") {
+                file.write_all(b"\n")?;
+            }
+            return Ok(Self { path, file, install });
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = File::create(&path)?;
+        let mut journal = Self {
+            path,
+            file,
+            install: UnfinishedInstall {
+                version: version.name(),
+                version_type: version.version_type(),
+                json_url: version.json_url(),
+                planned: Vec::new(),
+                completed: HashSet::new(),
+                launchable: false,
+            },
+        };
+        journal.append(Record::Started {
+            version: version.name(),
+            version_type: version.version_type(),
+            json_url: version.json_url(),
+        })?;
+        Ok(journal)
+    }
+
+    /// Reads a journal, a torn last line from a crash is ignored
+    pub fn read(path: &Path) -> io::Result<UnfinishedInstall> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let first = lines.next().transpose()?.unwrap_or_default();
+        let mut install = match serde_json::from_str(&first) {
+            Ok(Record::Started { version, version_type, json_url }) => UnfinishedInstall {
+                version,
+                version_type,
+                json_url,
+                planned: Vec::new(),
+                completed: HashSet::new(),
+                launchable: false,
+            },
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} does not start with the install it records", path.display()),
+                ));
+            }
+        };
+        for line in lines {
+            match serde_json::from_str::<Record>(&line?) {
+                Ok(record) => install.apply(record),
+                Err(e) => log::warn!("Skipping broken line of {}: {}", path.display(), e),
+            }
+        }
+        Ok(install)
+    }
+
+    /// Installs of the `versions` directory that did not complete
+    pub fn unfinished(minecraft_path: &str) -> Vec<UnfinishedInstall> {
+        let Ok(entries) = fs::read_dir(Path::new(minecraft_path).join("versions")) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join(JOURNAL_FILE))
+            .filter(|path| path.exists())
+            .filter_map(|path| match Self::read(&path) {
+                Ok(install) => Some(install),
+                Err(e) => {
+                    log::warn!("Failed to read {}: {}", path.display(), e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn install(&self) -> &UnfinishedInstall {
+        &self.install
+    }
+
+    /// Records the files the install needs
+    pub fn plan(&mut self, files: &[FileData]) -> io::Result<()> {
+        let files: Vec<PlannedFile> = files
+            .iter()
+            .map(|f| PlannedFile { path: f.path().to_string(), size: f.expected_size() })
+            .collect();
+        self.append(Record::Planned { files })?;
+        self.file.sync_data()
+    }
+
+    pub fn complete(&mut self, path: &str) -> io::Result<()> {
+        if self.install.is_completed(path) {
+            return Ok(());
+        }
+        self.append(Record::Completed { path: path.to_string() })
+    }
+
+    pub fn set_launchable(&mut self) -> io::Result<()> {
+        if self.install.is_launchable() {
+            return Ok(());
+        }
+        self.append(Record::Launchable)?;
+        self.file.sync_data()
+    }
+
+    /// The install completed, the journal is removed
+    pub fn finish(self) -> io::Result<()> {
+        fs::remove_file(&self.path)
+    }
+
+    fn append(&mut self, record: Record) -> io::Result<()> {
+        let mut line = serde_json::to_string(&record).map_err(io::Error::other)?;
+        line.push('\n');
+        // a single write per record, so a crash can only tear the last line
+        self.file.write_all(line.as_bytes())?;
+        self.install.apply(record);
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use std::io;
 use serde::{Deserialize, Serialize};
 use crate::versions::version_json::VersionType;
+use crate::downloader::transport::{DefaultTransport, Transport};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Manifest {
@@ -32,18 +33,16 @@ impl Clone for VersionInfo {
 }
 impl Manifest {
     pub async fn get_version_manifest(versions_manifest_link: &str) -> io::Result<Manifest> {
-        let response = reqwest::get(versions_manifest_link)
-            .await;
-        match response {
-            Ok(res) => {
-                Ok(res.json::<Manifest>()
-                    .await
-                    .expect("failed to parse versions"))
-            }
-            Err(e) => {
-                Err(io::Error::new(io::ErrorKind::Other, e))
-            }
-        }
+        Self::fetch(versions_manifest_link, &DefaultTransport::default()).await
+    }
+
+    /// Loads the manifest through the given transport, e.g. from a local fixture tree
+    pub async fn fetch(versions_manifest_link: &str, transport: &dyn Transport) -> io::Result<Manifest> {
+        let content = transport
+            .fetch_bytes(versions_manifest_link)
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        serde_json::from_slice(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 
     pub fn get_version_by_id(&self, version: &str) -> io::Result<VersionInfo> {
@@ -2,6 +2,10 @@ pub mod version;
 pub mod manifest;
 pub mod version_manager;
 pub mod verifier;
+pub mod journal;
+pub mod rules;
+pub mod natives;
+pub mod assets;
 mod downloader;
 pub mod version_json;
 
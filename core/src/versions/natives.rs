@@ -0,0 +1,78 @@
+use crate::versions::rules::LaunchContext;
+use crate::versions::version_json::{Library, VersionJson};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// Extensions of the native libraries inside the natives jars of the new form
+const NATIVE_EXTENSIONS: [&str; 4] = ["so", "dll", "dylib", "jnilib"];
+
+/// Where the natives of a version are extracted, `versions/<id>/natives`
+pub fn natives_path(minecraft_path: &str, version_name: &str) -> PathBuf {
+    Path::new(minecraft_path).join("versions").join(version_name).join("natives")
+}
+
+/// Extracts the natives jars the rules allow on `context` into the natives directory of
+/// the version, replacing what was there. Returns the number of files extracted.
+pub fn extract_natives(
+    version_json: &VersionJson,
+    minecraft_path: &str,
+    version_name: &str,
+    context: &LaunchContext,
+) -> io::Result<usize> {
+    let target = natives_path(minecraft_path, version_name);
+    if target.exists() {
+        fs::remove_dir_all(&target)?;
+    }
+    fs::create_dir_all(&target)?;
+
+    let libraries_path = Path::new(minecraft_path).join("libraries");
+    let mut extracted = 0;
+    for library in version_json.get_libraries_for(context) {
+        for jar in library.native_files(context) {
+            let Some(path) = jar.path() else { continue };
+            extracted += extract_jar(&library, &libraries_path.join(path), &target)?;
+        }
+    }
+    log::info!("{} natives of {} extracted to {}", extracted, version_name, target.display());
+    Ok(extracted)
+}
+
+/// Jars of the old form keep their layout minus the `extract.exclude` entries. Jars of
+/// the new form nest the libraries in os and arch directories, only those are taken,
+/// flattened into `target`.
+fn extract_jar(library: &Library, jar: &Path, target: &Path) -> io::Result<usize> {
+    let mut archive = ZipArchive::new(File::open(jar)?).map_err(io::Error::other)?;
+    let flatten = !library.has_classifiers();
+    let mut extracted = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i).map_err(io::Error::other)?;
+        if entry.is_dir() || library.extract_excludes().iter().any(|e| entry.name().starts_with(e.as_str())) {
+            continue;
+        }
+        // entries like `../../.bashrc` would be written outside of `target`
+        let Some(name) = entry.enclosed_name() else {
+            log::warn!("Skipping {} of {}, it points outside of the natives directory", entry.name(), jar.display());
+            continue;
+        };
+        let destination = if flatten {
+            let is_native = name
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| NATIVE_EXTENSIONS.contains(&e));
+            match name.file_name() {
+                Some(file_name) if is_native => target.join(file_name),
+                _ => continue,
+            }
+        } else {
+            target.join(name)
+        };
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(&destination)?)?;
+        extracted += 1;
+    }
+    Ok(extracted)
+}
@@ -0,0 +1,244 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+
+// +============================+
+//             Rules
+// +============================+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Allow,
+    Disallow,
+}
+
+/// Operating system a rule applies to, every field given has to match
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct OsRule {
+    pub name: Option<String>,
+    pub arch: Option<String>,
+    /// Regex matched against the version of the operating system
+    pub version: Option<String>,
+}
+
+/// Rule of a version json, it applies its action when its os and features match
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Rule {
+    pub action: RuleAction,
+    pub os: Option<OsRule>,
+    pub features: Option<HashMap<String, bool>>,
+}
+
+impl Rule {
+    pub fn matches(&self, context: &LaunchContext) -> bool {
+        let os_matches = self.os.as_ref().is_none_or(|os| {
+            os.name.as_ref().is_none_or(|name| *name == context.os_name)
+                && os.arch.as_ref().is_none_or(|arch| *arch == context.os_arch)
+                && os.version.as_ref().is_none_or(|version| match Regex::new(version) {
+                    Ok(regex) => regex.is_match(&context.os_version),
+                    Err(e) => {
+                        log::warn!("Invalid os version rule {}: {}", version, e);
+                        false
+                    }
+                })
+        });
+        let features_match = self.features.as_ref().is_none_or(|features| {
+            features.iter().all(|(feature, expected)| context.has_feature(feature) == *expected)
+        });
+        os_matches && features_match
+    }
+}
+
+/// The last matching rule decides, nothing matching disallows. No rules at all allows.
+pub fn is_allowed(rules: Option<&[Rule]>, context: &LaunchContext) -> bool {
+    let Some(rules) = rules else { return true };
+    if rules.is_empty() {
+        return true;
+    }
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(context))
+        .is_some_and(|rule| rule.action == RuleAction::Allow)
+}
+
+// +============================+
+//         LaunchContext
+// +============================+
+
+/// What the rules of a version json are evaluated against: the system the game runs on
+/// and the features of the launch
+#[derive(Debug, Clone)]
+pub struct LaunchContext {
+    os_name: String,
+    os_arch: String,
+    os_version: String,
+    features: HashMap<String, bool>,
+}
+
+impl LaunchContext {
+    /// The running system, with every feature off
+    pub fn current() -> Self {
+        let os_name = match env::consts::OS {
+            "macos" => "osx",
+            os => os,
+        };
+        let os_arch = match env::consts::ARCH {
+            "aarch64" => "arm64",
+            arch => arch,
+        };
+        Self {
+            os_name: os_name.to_string(),
+            os_arch: os_arch.to_string(),
+            os_version: os_version(),
+            features: HashMap::new(),
+        }
+    }
+
+    /// Name as written in the rules: `windows`, `osx` or `linux`
+    pub fn os_name(mut self, name: &str) -> Self {
+        self.os_name = name.to_string();
+        self
+    }
+
+    pub fn os_arch(mut self, arch: &str) -> Self {
+        self.os_arch = arch.to_string();
+        self
+    }
+
+    pub fn os_version(mut self, version: &str) -> Self {
+        self.os_version = version.to_string();
+        self
+    }
+
+    pub fn feature(mut self, feature: &str, enabled: bool) -> Self {
+        self.features.insert(feature.to_string(), enabled);
+        self
+    }
+
+    pub fn is_demo_user(self, demo: bool) -> Self {
+        self.feature("is_demo_user", demo)
+    }
+
+    pub fn has_custom_resolution(self, custom: bool) -> Self {
+        self.feature("has_custom_resolution", custom)
+    }
+
+    pub fn get_os_name(&self) -> &str {
+        &self.os_name
+    }
+
+    pub fn get_os_arch(&self) -> &str {
+        &self.os_arch
+    }
+
+    /// Features never set are off
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.get(feature).copied().unwrap_or(false)
+    }
+}
+
+impl Default for LaunchContext {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+/// Version of the running system, empty when it can't be found
+fn os_version() -> String {
+    let version = if cfg!(target_os = "linux") {
+        std::fs::read_to_string("/proc/sys/kernel/osrelease").ok()
+    } else if cfg!(target_os = "macos") {
+        command_output("sw_vers", &["-productVersion"])
+    } else if cfg!(target_os = "windows") {
+        // "Microsoft Windows [Version 10.0.19045.4170]"
+        command_output("cmd", &["/C", "ver"]).and_then(|out| {
+            out.trim().rsplit(' ').next().map(|v| v.trim_end_matches(']').to_string())
+        })
+    } else {
+        None
+    };
+    version.map(|v| v.trim().to_string()).unwrap_or_default()
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    String::from_utf8(output.stdout).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Vec<Rule> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn linux() -> LaunchContext {
+        LaunchContext::current().os_name("linux").os_arch("x86_64").os_version("6.1.0")
+    }
+
+    #[test]
+    fn no_rules_allow() {
+        assert!(is_allowed(None, &linux()));
+        assert!(is_allowed(Some(&[]), &linux()));
+    }
+
+    #[test]
+    fn no_matching_rule_disallows() {
+        let rules = parse(r#"[{"action": "allow", "os": {"name": "windows"}}]"#);
+        assert!(!is_allowed(Some(&rules), &linux()));
+        assert!(is_allowed(Some(&rules), &linux().os_name("windows")));
+    }
+
+    #[test]
+    fn last_matching_rule_decides() {
+        let rules = parse(r#"[{"action": "allow"}, {"action": "disallow", "os": {"name": "osx"}}]"#);
+        assert!(is_allowed(Some(&rules), &linux()));
+        assert!(!is_allowed(Some(&rules), &linux().os_name("osx")));
+
+        let reversed = parse(r#"[{"action": "disallow", "os": {"name": "osx"}}, {"action": "allow"}]"#);
+        assert!(is_allowed(Some(&reversed), &linux().os_name("osx")));
+    }
+
+    #[test]
+    fn os_arch_must_match() {
+        let rules = parse(r#"[{"action": "allow", "os": {"name": "windows", "arch": "x86"}}]"#);
+        let windows = linux().os_name("windows");
+        assert!(is_allowed(Some(&rules), &windows.clone().os_arch("x86")));
+        assert!(!is_allowed(Some(&rules), &windows.os_arch("x86_64")));
+    }
+
+    #[test]
+    fn os_version_is_a_regex() {
+        let rules = parse(r#"[{"action": "allow", "os": {"name": "osx", "version": "^10\\.5\\.\\d$"}}]"#);
+        let osx = linux().os_name("osx");
+        assert!(is_allowed(Some(&rules), &osx.clone().os_version("10.5.8")));
+        assert!(!is_allowed(Some(&rules), &osx.clone().os_version("10.15.7")));
+    }
+
+    #[test]
+    fn invalid_version_regex_never_matches() {
+        let rules = parse(r#"[{"action": "allow", "os": {"version": "("}}]"#);
+        assert!(!is_allowed(Some(&rules), &linux()));
+    }
+
+    #[test]
+    fn features_must_all_match() {
+        let rules = parse(
+            r#"[{"action": "allow", "features": {"is_demo_user": false, "has_custom_resolution": true}}]"#,
+        );
+        assert!(!is_allowed(Some(&rules), &linux()));
+        assert!(is_allowed(Some(&rules), &linux().has_custom_resolution(true)));
+        assert!(!is_allowed(Some(&rules), &linux().has_custom_resolution(true).is_demo_user(true)));
+    }
+
+    #[test]
+    fn unset_features_are_off() {
+        let rules = parse(r#"[{"action": "allow", "features": {"has_quick_plays_support": false}}]"#);
+        assert!(is_allowed(Some(&rules), &linux()));
+        assert!(!is_allowed(Some(&rules), &linux().feature("has_quick_plays_support", true)));
+    }
+}
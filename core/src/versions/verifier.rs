@@ -1,5 +1,8 @@
+use crate::downloader::checksum::Checksums;
 use crate::versions::version_json::{AssetsJson, VersionJson, VersionType};
 use crate::launcher::launcher_config::LauncherConfig;
+use crate::versions::journal::InstallJournal;
+use crate::versions::rules::LaunchContext;
 use crate::versions::version::{StandardVersion, Version, VersionState};
 use std::io;
 use std::path::{Path, PathBuf};
@@ -11,13 +14,19 @@ use hex;
 
 pub struct VersionVerifier;
 impl VersionVerifier {
+    /// The version json is in place and the install either completed or got far enough to play
     pub fn is_installed(version: &mut Box<(dyn Version + 'static)>) -> bool {
-        if Path::new(&LauncherConfig::import_config().minecraft_path)
-            .join("versions")
-            .join(version.name())
-            .join(format!("{}.json", version.name()))
-            .as_path()
-            .exists()
+        let minecraft_path = LauncherConfig::import_config().minecraft_path;
+        let unfinished = InstallJournal::exists(&minecraft_path, &version.name())
+            && !InstallJournal::read(&InstallJournal::path(&minecraft_path, &version.name()))
+                .is_ok_and(|install| install.is_launchable());
+        if !unfinished
+            && Path::new(&minecraft_path)
+                .join("versions")
+                .join(version.name())
+                .join(format!("{}.json", version.name()))
+                .as_path()
+                .exists()
         {
             version.set_state(VersionState::INSTALLED(true));
             return true;
@@ -26,67 +35,77 @@ impl VersionVerifier {
         false
     }
 
-    // TODO: mejorar verificador con sha256
+    /// Checks the client, libraries and assets against the sizes and hashes of their jsons
     pub fn verify_installation(version: &mut Box<(dyn Version + 'static)>) -> bool {
-        // TODO: version file an client sha256
         let LauncherConfig { minecraft_path, .. } = LauncherConfig::import_config();
-        let version_json = VersionJson::get_from_local(&minecraft_path, &version.name()).unwrap();
-        let assets_paths: Vec<Box<PathBuf>> = {
-            AssetsJson::from_local(
-                Path::new(&minecraft_path)
-                    .join("assets")
-                    .join("indexes")
-                    .join(format!("{}.json", &version_json.get_asset_index().id).as_str())
-                    .as_path(),
-            )
-            .get_assets_directories()
-            .into_iter()
-            .map(|p| {
-                Box::new(
-                    Path::new(&minecraft_path)
-                        .join("assets")
-                        .join("objects")
-                        .join(p),
-                )
-            })
-            .collect()
+        let minecraft_path = Path::new(&minecraft_path);
+        let version_json = match VersionJson::get_from_local(&minecraft_path.to_string_lossy(), &version.name()) {
+            Ok(version_json) => version_json,
+            Err(e) => {
+                log::error!("{e}");
+                return false;
+            }
+        };
+        let assets_path = minecraft_path
+            .join("assets")
+            .join("indexes")
+            .join(format!("{}.json", &version_json.get_asset_index().id));
+        let assets: Vec<(PathBuf, Checksums)> = match AssetsJson::from_local(&assets_path) {
+            Ok(assets_json) => assets_json
+                .objects
+                .into_values()
+                .map(|asset| {
+                    (
+                        minecraft_path
+                            .join("assets")
+                            .join("objects")
+                            .join(asset.object_path()),
+                        Checksums::new().sha1(asset.hash).size(asset.size),
+                    )
+                })
+                .collect(),
+            Err(e) => {
+                log::error!("{e}");
+                return false;
+            }
         };
 
-        let libraries_paths: Vec<Box<PathBuf>> = {
+        let libraries: Vec<(PathBuf, Checksums)> = {
+            let context = LaunchContext::current();
             version_json
-                .get_libraries()
-                .into_iter()
-                .map(|l| {
-                    Box::new(
-                        Path::new(&minecraft_path)
-                            .join("libraries")
-                            .join(l.get_path()),
-                    )
+                .get_libraries_for(&context)
+                .iter()
+                .flat_map(|l| l.files(&context))
+                .filter_map(|jar| {
+                    Some((
+                        minecraft_path.join("libraries").join(jar.path()?),
+                        Checksums::new().sha1(jar.sha1()).size(jar.size()),
+                    ))
                 })
                 .collect()
         };
 
-        let assets_number = assets_paths.len();
-        let libraries_number = libraries_paths.len();
-        let mut verified = 0usize;
+        let client = (
+            minecraft_path
+                .join("versions")
+                .join(version.name())
+                .join(format!("{}.jar", version.name())),
+            Checksums::new()
+                .sha1(version_json.get_client_sha1())
+                .size(version_json.get_client_size()),
+        );
+
+        let assets_number = assets.len();
+        let libraries_number = libraries.len();
         log::info!(
             "Verifying Minecraft version.
             assets: {assets_number}, libraries: {libraries_number}"
         );
 
-        // verify assets
-        for path in &assets_paths {
-            if !path.as_path().exists() {
-                log::info!("Assets path does not exist: {:?}", path);
-                return false;
-            }
-            verified += 1;
-        }
-
-        // verify libraries
-        for path in &libraries_paths {
-            if !path.as_path().exists() {
-                log::info!("Library path does not exist: {:?}", path);
+        let mut verified = 0usize;
+        for (path, checksums) in std::iter::once(&client).chain(&libraries).chain(&assets) {
+            if let Err(e) = checksums.verify_file(path) {
+                log::info!("Verification of {:?} failed: {}", path, e);
                 return false;
             }
             verified += 1;
@@ -98,7 +117,8 @@ impl VersionVerifier {
     pub fn from_local(name: String) -> io::Result<Box<(dyn Version + 'static)>> {
         //TODO: adapt for forge, etc...
 
-        let version_json = VersionJson::get_from_local(&LauncherConfig::import_config().minecraft_path, &name).unwrap();
+        let version_json = VersionJson::get_from_local(&LauncherConfig::import_config().minecraft_path, &name)
+            .map_err(io::Error::other)?;
         
         match version_json.get_type() {
             VersionType::RELEASE
@@ -1,6 +1,7 @@
 use crate::launcher::launcher_config::LauncherConfig;
+use crate::versions::rules::{self, LaunchContext, Rule};
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
@@ -21,6 +22,18 @@ pub enum VersionType {
     OldAlpha,
 }
 
+impl VersionType {
+    /// Name as written in the version json
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VersionType::RELEASE => "release",
+            VersionType::SNAPSHOT => "snapshot",
+            VersionType::OldBeta => "old_beta",
+            VersionType::OldAlpha => "old_alpha",
+        }
+    }
+}
+
 // +============================+
 //          VersionJson          
 // +============================+
@@ -29,8 +42,14 @@ pub enum VersionType {
 #[derive(Debug, Deserialize)]
 pub struct VersionJson {
     id: String,
+    /// Missing before 1.13, which have `minecraftArguments` instead
+    #[serde(default)]
     arguments: Arguments,
+    /// Game arguments of the versions before 1.13, separated by spaces
+    #[serde(rename = "minecraftArguments")]
+    minecraft_arguments: Option<String>,
     downloads: Downloads,
+    #[serde(default)]
     libraries: Vec<Library>,
     #[serde(rename = "mainClass")]
     main_class: String,
@@ -48,15 +67,13 @@ impl VersionJson {
             .join(version)
             .join(format!("{}.json", version));
 
-        let mut file = File::open(path.as_path()).expect(&format!(
-            "Failed to open version.json on {}",
-            path.display()
-        ));
+        let mut file = File::open(path.as_path())
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
         let mut content = String::new();
         file.read_to_string(&mut content)
-            .expect("Failed to read launcher_profiles.json");
-        let json: VersionJson =
-            serde_json::from_str(&content).expect("Failed to parse launcher_profiles.json");
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let json: VersionJson = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
         Ok(json)
     }
     
@@ -64,26 +81,58 @@ impl VersionJson {
         self.downloads.client.url.clone()
     }
 
-    pub fn get_client_mappings_url(&self) -> String {
-        self.downloads.client_mappings.url.clone()
+    pub fn get_client_sha1(&self) -> String {
+        self.downloads.client.sha1.clone()
+    }
+
+    pub fn get_client_size(&self) -> u64 {
+        self.downloads.client.size
+    }
+
+    /// Obfuscation mappings of the client, published since 1.14.4
+    pub fn get_client_mappings(&self) -> Option<Download> {
+        self.downloads.client_mappings.clone()
     }
 
+    /// Arguments of the version, the `minecraftArguments` of the versions before 1.13
+    /// become game arguments without rules
     pub fn get_arguments(&self) -> Arguments {
-        self.arguments.clone()
+        match &self.minecraft_arguments {
+            Some(legacy) if self.arguments.game.is_empty() => Arguments {
+                game: legacy.split_whitespace().map(|a| ArgumentRule::Simple(a.to_string())).collect(),
+                jvm: self.arguments.jvm.clone(),
+            },
+            _ => self.arguments.clone(),
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.minecraft_arguments.is_some()
     }
 
     pub fn get_libraries(&self) -> Vec<Library> {
         self.libraries.clone()
     }
 
-    pub fn get_libraries_path(&self, minecraft_path: &str) -> Vec<String> {
+    /// Libraries the rules allow on `context`
+    pub fn get_libraries_for(&self, context: &LaunchContext) -> Vec<Library> {
+        self.libraries
+            .iter()
+            .filter(|library| library.is_allowed(context))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_libraries_path(&self, minecraft_path: &str, context: &LaunchContext) -> Vec<String> {
         let libraries = self
             .libraries
             .iter()
-            .map(|library| -> String {
+            .filter(|library| library.is_allowed(context))
+            .filter_map(|library| library.artifact()?.path())
+            .map(|path| -> String {
                 Path::new(minecraft_path)
                     .join("libraries")
-                    .join(library.get_path())
+                    .join(path)
                     .as_path()
                     .to_str()
                     .unwrap()
@@ -93,21 +142,18 @@ impl VersionJson {
         libraries
     }
 
-    // pub fn filter_libraries(&self);
-
     pub fn get_asset_index(&self) -> AssetIndex {
         self.asset_index.clone()
     }
     
-    pub fn get_assets_json(&self) -> AssetsJson {
-        let assets = AssetsJson::from_local(
+    pub fn get_assets_json(&self) -> Result<AssetsJson, String> {
+        AssetsJson::from_local(
             Path::new(&LauncherConfig::import_config().minecraft_path)
                 .join("assets")
                 .join("indexes")
                 .join(format!("{}.json", self.asset_index.id).as_str())
                 .as_path(),
-        );
-        assets
+        )
     }
     
     pub fn get_type(&self) -> VersionType {
@@ -129,7 +175,9 @@ impl VersionJson {
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct Arguments {
+    #[serde(default)]
     game: Vec<ArgumentRule>,
+    #[serde(default)]
     jvm: Vec<ArgumentRule>,
 }
 
@@ -141,6 +189,16 @@ impl Arguments {
     pub fn get_jvm(&self) -> &Vec<ArgumentRule> {
         &self.jvm
     }
+
+    /// Game arguments the rules allow on `context`, placeholders not substituted
+    pub fn game_values(&self, context: &LaunchContext) -> Vec<String> {
+        self.game.iter().flat_map(|arg| arg.values(context)).collect()
+    }
+
+    /// JVM arguments the rules allow on `context`, placeholders not substituted
+    pub fn jvm_values(&self, context: &LaunchContext) -> Vec<String> {
+        self.jvm.iter().flat_map(|arg| arg.values(context)).collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -154,20 +212,26 @@ pub enum ArgumentRule {
     },
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct Rule {
-    action: String,
-    features: Option<Features>,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct Features {
-    is_demo_user: Option<bool>,
-    has_custom_resolution: Option<bool>,
-    has_quick_plays_support: Option<bool>,
-    is_quick_play_singleplayer: Option<bool>,
-    is_quick_play_multiplayer: Option<bool>,
-    is_quick_play_realms: Option<bool>,
+impl ArgumentRule {
+    /// The argument values, none when its rules disallow it on `context`
+    pub fn values(&self, context: &LaunchContext) -> Vec<String> {
+        match self {
+            ArgumentRule::Simple(value) => vec![value.clone()],
+            ArgumentRule::Complex { rules, value } => {
+                if !rules::is_allowed(rules.as_deref(), context) {
+                    return Vec::new();
+                }
+                match value {
+                    serde_json::Value::String(value) => vec![value.clone()],
+                    serde_json::Value::Array(values) => values
+                        .iter()
+                        .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                        .collect(),
+                    _ => Vec::new(),
+                }
+            }
+        }
+    }
 }
 
 // +============================+
@@ -177,7 +241,7 @@ pub struct Features {
 #[derive(Debug, Deserialize)]
 pub struct Downloads {
     client: Download,
-    client_mappings: Download
+    client_mappings: Option<Download>,
     // TODO: server & server mapping
 }
 
@@ -186,71 +250,128 @@ pub struct Download { // TODO: File data
     path: Option<String>,
     url: String,
     sha1: String,
+    size: u64,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct Library {
-    downloads: LibraryDownload,
-    name: String,
-    rules: Option<Vec<LibraryRule>>,
-}
+impl Download {
+    /// Path relative to the `libraries` directory, only libraries have one
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct LibraryRule {
-    os: Option<Os>,
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn sha1(&self) -> &str {
+        &self.sha1
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
-pub struct Os {
+pub struct Library {
+    #[serde(default)]
+    downloads: LibraryDownload,
     name: String,
+    rules: Option<Vec<Rule>>,
+    /// Classifier of the natives jar by os, before 1.19. e.g. `"windows": "natives-windows-${arch}"`
+    natives: Option<HashMap<String, String>>,
+    extract: Option<ExtractRules>,
 }
+
 impl Library {
     pub fn name(&self) -> &str {
         self.name.as_str()
     }
 
-    pub fn get_download_url(&self) -> String {
-        self.downloads.artifact.url.clone()
+    /// The jar of the library, natives of the old form only have classifiers
+    pub fn artifact(&self) -> Option<&Download> {
+        self.downloads.artifact.as_ref()
+    }
+
+    /// Natives jar of the old form for the system of `context`
+    pub fn native_classifier(&self, context: &LaunchContext) -> Option<&Download> {
+        let classifier = self.natives.as_ref()?.get(context.get_os_name())?;
+        let bits = if context.get_os_arch() == "x86" { "32" } else { "64" };
+        let classifier = classifier.replace("${arch}", bits);
+        self.downloads.classifiers.as_ref()?.get(&classifier)
+    }
+
+    /// Every jar of the library to download on `context`
+    pub fn files(&self, context: &LaunchContext) -> Vec<&Download> {
+        self.artifact().into_iter().chain(self.native_classifier(context)).collect()
     }
 
-    pub fn get_path(&self) -> String {
-        self.downloads.artifact.path.clone().unwrap()
+    /// Jars holding native code to extract on `context`, of either form. The natives of the
+    /// new form for other archs are left out, their libraries have the same file names.
+    pub fn native_files(&self, context: &LaunchContext) -> Vec<&Download> {
+        let artifact = self
+            .artifact()
+            .filter(|_| self.is_native() && self.native_arch() == Some(context.get_os_arch()));
+        artifact.into_iter().chain(self.native_classifier(context)).collect()
     }
 
-    pub fn get_sha1(&self) -> String {
-        self.downloads.artifact.sha1.clone()
+    /// Arch of a natives artifact of the new form, as named in the rules: no suffix is
+    /// `x86_64`, e.g. `natives-windows` and `natives-windows-arm64`
+    pub fn native_arch(&self) -> Option<&str> {
+        let classifier = self.name.rsplit(':').next()?.strip_prefix("natives-")?;
+        let arch = match classifier.split_once('-') {
+            None => "x86_64",
+            Some((_, "arm64")) => "arm64",
+            Some((_, "x86")) => "x86",
+            Some((_, "arm32")) => "arm",
+            Some((_, arch)) => arch,
+        };
+        Some(arch)
     }
 
+    /// Natives artifact of the new form, e.g. `org.lwjgl:lwjgl:3.3.3:natives-linux`
     pub fn is_native(&self) -> bool {
         self.name.contains(":natives")
     }
 
-    pub fn filter_native_by_os(&self) -> bool {
-        let mut current_os: String = { env::consts::OS.to_string() };
-        let mut current_arch: String = { env::consts::ARCH.to_string() }; // TODO: use arch
-        if let Some(rules) = &self.rules {
-            if current_os == "macos".to_owned() {
-                current_os = "osx".to_owned();
-            }
-            for r in rules {
-                if let Some(os) = r.os.as_ref() {
-                    if os.name == current_os {
-                        return true;
-                    }
-                }
-            }
-        } else {
-            if self.name.contains(&format!(":natives-{}", current_os)) {
-                return true;
-            }
+    /// Has a natives jar of the old form
+    pub fn has_classifiers(&self) -> bool {
+        self.natives.is_some()
+    }
+
+    /// Entries of the natives jars that are not extracted, as path prefixes
+    pub fn extract_excludes(&self) -> &[String] {
+        self.extract.as_ref().map(|e| e.exclude.as_slice()).unwrap_or_default()
+    }
+
+    pub fn rules(&self) -> Option<&[Rule]> {
+        self.rules.as_deref()
+    }
+
+    /// The rules allow the library on `context`. Natives without rules only go
+    /// to the system their classifier names.
+    pub fn is_allowed(&self, context: &LaunchContext) -> bool {
+        if self.rules.is_none() && self.is_native() {
+            let os = match context.get_os_name() {
+                "osx" => "macos",
+                os => os,
+            };
+            return self.name.contains(&format!(":natives-{}", os));
         }
-        false
+        rules::is_allowed(self.rules(), context)
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct LibraryDownload {
-    artifact: Download,
+    artifact: Option<Download>,
+    classifiers: Option<HashMap<String, Download>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExtractRules {
+    #[serde(default)]
+    exclude: Vec<String>,
 }
 
 // +============================+
@@ -262,6 +383,7 @@ pub struct AssetIndex {
     pub id: String,
     pub url: String,
     pub sha1: String,
+    pub size: u64,
 }
 
 // +============================+
@@ -272,30 +394,40 @@ pub struct AssetIndex {
 #[derive(Debug, Clone, Deserialize)]
 pub struct AssetsJson {
     pub objects: std::collections::HashMap<String, Asset>,
+    /// Before 1.7.10 the game reads the assets by name from `assets/virtual/<index>`
+    #[serde(rename = "virtual", default)]
+    pub is_virtual: bool,
+    /// Before 1.6 the game reads the assets by name from `<gameDir>/resources`
+    #[serde(default)]
+    pub map_to_resources: bool,
 }
 impl AssetsJson {
-    pub fn from_local(assets_path: &Path) -> Self {
-        let mut file = File::open(assets_path).expect("Failed to open assets.json");
+    pub fn from_local(assets_path: &Path) -> Result<Self, String> {
+        let mut file = File::open(assets_path)
+            .map_err(|e| format!("Failed to open {}: {}", assets_path.display(), e))?;
         let mut content = String::new();
         file.read_to_string(&mut content)
-            .expect("Failed to read launcher_profiles.json");
-        let json: AssetsJson =
-            serde_json::from_str(&content).expect("Failed to parse launcher_profiles.json");
-        json
+            .map_err(|e| format!("Failed to read {}: {}", assets_path.display(), e))?;
+        Self::parse(&content).map_err(|e| format!("Failed to parse {}: {}", assets_path.display(), e))
+    }
+
+    /// Parses an asset index, the entries without a valid sha1 are left out: their object
+    /// path can't be built and no download would pass the check
+    pub fn parse(content: &str) -> serde_json::Result<Self> {
+        let mut json: AssetsJson = serde_json::from_str(content)?;
+        json.objects.retain(|name, asset| {
+            let valid = asset.has_valid_hash();
+            if !valid {
+                log::warn!("Skipping asset {}, its hash {:?} is not a sha1", name, asset.hash);
+            }
+            valid
+        });
+        Ok(json)
     }
 }
 impl AssetsJson {
     pub fn get_assets_directories(&self) -> Vec<String> {
-        let directories: Vec<String> = self
-            .objects
-            .clone()
-            .into_iter()
-            .map(|(_, h)| {
-                let hash = h.hash;
-                let dir = format!("{}/{}", &hash[..2], hash);
-                dir
-            })
-            .collect();
+        let directories: Vec<String> = self.objects.values().map(Asset::object_path).collect();
         directories
     }
 }
@@ -303,4 +435,47 @@ impl AssetsJson {
 #[derive(Debug, Clone, Deserialize)]
 pub struct Asset {
     pub hash: String,
+    pub size: u64,
+}
+impl Asset {
+    /// Asset objects are named by their sha1, 40 hex digits
+    pub fn has_valid_hash(&self) -> bool {
+        self.hash.len() == 40 && self.hash.bytes().all(|b| b.is_ascii_hexdigit())
+    }
+
+    /// Path of the object in `assets/objects`, `<first two digits of the hash>/<hash>`
+    pub fn object_path(&self) -> String {
+        format!("{}/{}", self.hash.get(..2).unwrap_or_default(), self.hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asset_index_leaves_out_invalid_hashes() {
+        let assets = AssetsJson::parse(
+            r#"{"objects": {
+                "icons/icon_16x16.png": {"hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 3665},
+                "short": {"hash": "b", "size": 1},
+                "multibyte": {"hash": "éa4b6c7d8e9f00112233445566778899aabbccd", "size": 1},
+                "not_hex": {"hash": "zzf48ef6b5d0d23bbb02e17d04865216179f510a", "size": 1}
+            }}"#,
+        )
+        .unwrap();
+        assert_eq!(assets.objects.len(), 1);
+        assert_eq!(
+            assets.objects["icons/icon_16x16.png"].object_path(),
+            "bd/bdf48ef6b5d0d23bbb02e17d04865216179f510a"
+        );
+        assert!(!assets.is_virtual && !assets.map_to_resources);
+    }
+
+    #[test]
+    fn object_path_of_a_short_hash_does_not_panic() {
+        let asset = |hash: &str| Asset { hash: hash.to_string(), size: 0 };
+        assert_eq!(asset("b").object_path(), "/b");
+        assert_eq!(asset("aé").object_path(), "/aé");
+    }
 }
@@ -1,15 +1,19 @@
 use crate::versions::version_json::VersionType;
-use crate::downloader::downloader::DownloaderTracking;
+use crate::downloader::events::EventSender;
+use crate::downloader::transport::DefaultTransport;
+use crate::downloader::report::DownloadReport;
+use crate::tasks::progress::Progress;
+use crate::tasks::tasks::CancellationToken;
 use crate::launcher::launcher_config::{LauncherConfig, LauncherProfiles, LauncherSettings};
 use crate::versions::downloader::VersionDownloadTask;
 use crate::versions::manifest::Manifest;
 use crate::versions::verifier::VersionVerifier;
-use crate::versions::version::{StandardVersion, Version};
+use crate::versions::journal::{InstallJournal, UnfinishedInstall};
+use crate::versions::version::{StandardVersion, Version, VersionState};
 use std::path::Path;
 use std::sync::Arc;
 use std::{fs, io};
 use log;
-use tokio::sync::Mutex;
 
 pub struct VersionManager;
 
@@ -27,7 +31,18 @@ impl VersionManager {
 
     async fn versions_by_manifest() -> io::Result<Vec<Box<dyn Version>>> {
         let config = LauncherConfig::import_config();
-        let manifest = Manifest::get_version_manifest(&config.version_manifest_link).await?;
+        let transport = config
+            .local_transport()
+            .unwrap_or_else(|| Arc::new(DefaultTransport::default()));
+        let mut manifest = Err(io::Error::new(io::ErrorKind::NotFound, "No manifest url"));
+        for link in config.mirrors().candidates(&config.version_manifest_link) {
+            manifest = Manifest::fetch(&link, transport.as_ref()).await;
+            match &manifest {
+                Ok(_) => break,
+                Err(e) => log::warn!("Failed to fetch manifest from {link}: {e}"),
+            }
+        }
+        let manifest = manifest?;
 
         let settings = match LauncherProfiles::import_profiles() {
             Some(s) => s.settings().expect("Failed to import profiles"),
@@ -64,9 +79,20 @@ impl VersionManager {
 
         for path in versions_list {
             // TODO: multithread
-            versions.push(VersionVerifier::from_local(
-                path.unwrap().file_name().into_string().unwrap(),
-            )?)
+            let name = path.unwrap().file_name().into_string().unwrap();
+            // an install interrupted before its json was written has nothing to load
+            if !Path::new(&LauncherConfig::import_config().minecraft_path)
+                .join("versions")
+                .join(&name)
+                .join(format!("{}.json", name))
+                .exists()
+            {
+                continue;
+            }
+            match VersionVerifier::from_local(name) {
+                Ok(version) => versions.push(version),
+                Err(e) => log::warn!("Skipping local version: {e}"),
+            }
         }
         Ok(versions)
     }
@@ -77,18 +103,38 @@ impl VersionManager {
 
     pub async fn download_version(
         version: Box<(dyn Version + 'static)>,
-        progress: Arc<Mutex<DownloaderTracking>>,
-    ) -> io::Result<()> 
+        events: EventSender,
+        cancel: CancellationToken,
+        progress: Progress,
+    ) -> io::Result<DownloadReport>
     {
         //if VersionVerifier::is_installed(&mut version) {
         // TODO: verify version.jsn sha256 or download, verify installation
         //    return Ok(())
         //}
-        let progress_clone = progress.clone();
-        VersionDownloadTask::download_version(version, progress_clone)
-            .await
-            .expect("Can't Download the version");
-        Ok(())
+        VersionDownloadTask::download_version(version, events, cancel, progress).await
+    }
+
+    /// Installs interrupted by a crash, a restart or a cancel, they resume with `resume_install`
+    pub fn unfinished_installs() -> Vec<UnfinishedInstall> {
+        InstallJournal::unfinished(&LauncherConfig::import_config().minecraft_path)
+    }
+
+    /// Downloads what an unfinished install is missing
+    pub async fn resume_install(
+        install: &UnfinishedInstall,
+        events: EventSender,
+        cancel: CancellationToken,
+        progress: Progress,
+    ) -> io::Result<DownloadReport>
+    {
+        let version: Box<dyn Version> = Box::new(StandardVersion::new(
+            install.version(),
+            install.version_type(),
+            install.json_url(),
+            VersionState::INSTALLED(false),
+        ));
+        Self::download_version(version, events, cancel, progress).await
     }
 
     pub fn verify_version_installation(mut version: Box<(dyn Version + 'static)>) -> bool {
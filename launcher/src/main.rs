@@ -2,8 +2,6 @@ use std::env;
 mod tui;
 use core;
 use std::error::Error;
-use std::sync::Arc;
-use tokio::sync::Mutex;
 use core::launcher::{
     launcher_config::{
         LauncherConfig,
@@ -19,6 +17,10 @@ use core::versions::{
     version_manager::VersionManager
 };
 use core::downloader::downloader::DownloaderTracking;
+use core::downloader::events::{self, DownloadEvent};
+use core::tasks::progress::Progress;
+use core::tasks::tasks::CancellationToken;
+use tokio::sync::broadcast::error::RecvError;
 use core::users::UserBuilder;
 
 #[tokio::main]
@@ -31,13 +33,49 @@ async fn main() -> Result<(), Box<dyn Error>> {
         env_logger::init();
 
         
-        VersionManager::download_version(VersionBuilder::default()
+        let (events, mut receiver) = events::channel();
+        tokio::spawn(async move {
+            let mut tracking = DownloaderTracking::default();
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                tracking.apply(&event);
+                match event {
+                    DownloadEvent::PhaseChanged(state) => log::info!("{:?}", state),
+                    DownloadEvent::FileFinished { path, .. } => {
+                        let (done, total) = tracking.bytes();
+                        log::info!(
+                            "[{}/{}] {} ({} MB / {} MB, {:.1} MB/s, {} s left)",
+                            tracking.actual_progress(),
+                            tracking.total_progress(),
+                            path,
+                            done / 1_000_000,
+                            total / 1_000_000,
+                            tracking.rate() / 1_000_000.0,
+                            tracking.eta().map(|eta| eta.as_secs()).unwrap_or(0)
+                        )
+                    }
+                    DownloadEvent::FileFailed { path, error, .. } => log::error!("{}: {}", path, error),
+                    _ => {}
+                }
+            }
+        });
+        let report = VersionManager::download_version(VersionBuilder::default()
             .name("1.21.3")
             .state(VersionState::INSTALLED(false))
             .url("https://piston-meta.mojang.com/v1/packages/b64c551553e59c369f4a3529b15c570ac6b9b73e/1.21.3.json")
             .build().unwrap(),
-            Arc::new(Mutex::new(DownloaderTracking::default()))
+            events,
+            CancellationToken::new(),
+            Progress::new("install 1.21.3")
         ).await.expect("Failed to download version");
+        log::info!("{report}");
+        for failed in report.failed() {
+            log::error!("{}: {}", failed.file().path(), failed.outcome());
+        }
         
         /*
         let ml = MinecraftBuilder::new()
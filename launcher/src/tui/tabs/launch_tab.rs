@@ -1,7 +1,13 @@
 use core::users::User;
 use crate::core::downloader::downloader::DownloaderTracking;
+use crate::core::downloader::events::{self, EventSender};
+use crate::core::downloader::limiter::BandwidthLimiter;
+use crate::core::tasks::progress::Progress;
+use crate::core::tasks::tasks::CancellationToken;
+use crate::core::downloader::report::DownloadReport;
 use crate::core::launcher::launcher::MinecraftBuilder;
 use crate::core::users::UserBuilder;
+use crate::core::versions::journal::UnfinishedInstall;
 use crate::core::versions::version::Version;
 use crate::core::versions::version_manager::VersionManager;
 use crate::tui::app::Tab;
@@ -11,17 +17,29 @@ use ratatui::layout::{Alignment, Constraint, Layout, Rect};
 use ratatui::prelude::{StatefulWidget, Widget};
 use ratatui::style::{Color, Style};
 use ratatui::text::Line;
-use ratatui::widgets::{Block, BorderType, Borders, List, Paragraph};
+use ratatui::widgets::{Block, BorderType, Borders, Gauge, List, Paragraph};
+use std::io;
 use std::sync::Arc;
 use tokio::sync::{Mutex};
+use tokio::sync::broadcast::error::RecvError;
 use tui_widget_list::{ListBuilder, ListState, ListView};
 
+/// Bandwidth limits the '-' and '+' keys move between, above the max it is unlimited
+const MIN_BANDWIDTH: u64 = 256 * 1024;
+const MAX_BANDWIDTH: u64 = 64 * 1024 * 1024;
+
 #[derive(Clone, Default)]
 pub struct LaunchTab {
     cached_versions: Vec<Box<dyn Version>>,
     selected_version: Option<Box<dyn Version>>,
     selected_index: usize,
     download_progress: Option<Arc<Mutex<DownloaderTracking>>>,
+    download_report: Arc<Mutex<Option<DownloadReport>>>,
+    download_cancel: Option<CancellationToken>,
+    /// Steps of the running install
+    install_progress: Option<Progress>,
+    /// Installs a previous run did not complete
+    unfinished_installs: Vec<UnfinishedInstall>,
     state: LaunchTabState,
     list_state: ListState,
 }
@@ -47,6 +65,10 @@ impl LaunchTab {
             selected_version: None,
             selected_index: 0,
             download_progress: None,
+            download_report: Arc::new(Mutex::new(None)),
+            download_cancel: None,
+            install_progress: None,
+            unfinished_installs: VersionManager::unfinished_installs(),
             state: LaunchTabState::default(),
             list_state: ListState::default(),
         }
@@ -93,27 +115,58 @@ impl Tab for LaunchTab {
                 self.selected_version = self.cached_versions.get(self.selected_index).cloned();
             }
             KeyCode::Char('a') => { 
-                self.state = LaunchTabState::DOWNLOADING;
-                match &self.selected_version {
+                match self.selected_version.clone() {
                     Some(version) => {
-                        let progress = match &self.download_progress {
-                            Some(p) => { p.clone() }
-                            None => {
-                                let p = Arc::new(Mutex::new(DownloaderTracking::default()));
-                                self.download_progress = Some(p.clone());
-                                p.clone()
-                            }
-                        };
-                        let v = version.clone();
-                        log::info!("Starting download");
-                        tokio::spawn(async move {
-                            log::info!("Living in parallel");
-                            VersionManager::download_version(v, progress).await.expect("[LaunchTab] Downloading error");
+                        let name = format!("install {}", version.name());
+                        self.start_download(name, move |events, cancel, progress| {
+                            VersionManager::download_version(version, events, cancel, progress)
                         });
                     }
                     None => {log::info!("not selected")} // TODO: not version selected advice
                 }
             } // download
+            KeyCode::Char('r') => {
+                // the install of the selected version, or the first one left
+                let selected = self.selected_version.as_ref().map(|v| v.name());
+                let index = self.unfinished_installs
+                    .iter()
+                    .position(|i| Some(i.version()) == selected.as_deref())
+                    .or(if self.unfinished_installs.is_empty() { None } else { Some(0) });
+                match index {
+                    Some(index) => {
+                        let install = self.unfinished_installs.remove(index);
+                        log::info!("Resuming install of {}", install.version());
+                        let name = format!("resume {}", install.version());
+                        self.start_download(name, move |events, cancel, progress| async move {
+                            VersionManager::resume_install(&install, events, cancel, progress).await
+                        });
+                    }
+                    None => log::info!("No unfinished install to resume"),
+                }
+            } // resume unfinished install
+            KeyCode::Char('x') => {
+                if let Some(cancel) = self.download_cancel.take() {
+                    log::info!("Cancelling download");
+                    cancel.cancel();
+                    self.state = LaunchTabState::NORMAL;
+                }
+            } // cancel download
+            KeyCode::Char('-') => {
+                let limiter = BandwidthLimiter::global();
+                let rate = match limiter.rate() {
+                    Some(rate) => (rate / 2).max(MIN_BANDWIDTH),
+                    None => MAX_BANDWIDTH,
+                };
+                limiter.set_rate(Some(rate));
+            } // lower bandwidth limit
+            KeyCode::Char('+') => {
+                let limiter = BandwidthLimiter::global();
+                let rate = match limiter.rate() {
+                    Some(rate) if rate < MAX_BANDWIDTH => Some(rate * 2),
+                    _ => None,
+                };
+                limiter.set_rate(rate);
+            } // raise bandwidth limit
             KeyCode::Char('d') => {} // verify
             KeyCode::Char(' ') => {
                 self.state = LaunchTabState::LAUNCHING;
@@ -152,6 +205,48 @@ impl Widget for ListItem {
     }
 }
 
+impl LaunchTab {
+    /// Runs `download` in the background, its progress and report show in the tab
+    fn start_download<F, Fut>(&mut self, name: String, download: F)
+    where
+        F: FnOnce(EventSender, CancellationToken, Progress) -> Fut + Send + 'static,
+        Fut: Future<Output = io::Result<DownloadReport>> + Send + 'static,
+    {
+        self.state = LaunchTabState::DOWNLOADING;
+        let progress = match &self.download_progress {
+            Some(p) => { p.clone() }
+            None => {
+                let p = Arc::new(Mutex::new(DownloaderTracking::default()));
+                self.download_progress = Some(p.clone());
+                p.clone()
+            }
+        };
+        let (events, mut receiver) = events::channel();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => progress.lock().await.apply(&event),
+                    Err(RecvError::Lagged(n)) => log::warn!("[LaunchTab] {n} progress events lost"),
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        let cancel = CancellationToken::new();
+        self.download_cancel = Some(cancel.clone());
+        let steps = Progress::new(name);
+        self.install_progress = Some(steps.clone());
+        let report = self.download_report.clone();
+        log::info!("Starting download");
+        tokio::spawn(async move {
+            log::info!("Living in parallel");
+            match download(events, cancel, steps).await {
+                Ok(r) => *report.lock().await = Some(r),
+                Err(e) => log::error!("[LaunchTab] Downloading error: {e}"),
+            }
+        });
+    }
+}
+
 // renders
 impl LaunchTab {
     fn render_versions_selectioner(&mut self, area: Rect, buf: &mut Buffer) {
@@ -222,7 +317,7 @@ impl LaunchTab {
         let mut lines: Vec<Line> = if let Some(progress) = self.download_progress.clone() {
             let progress = progress.try_lock();
             let units_len = match &progress {
-                Ok(u) => {u.units().clone().iter().len()}
+                Ok(u) => {u.units().len()}
                 _ => {0}
             };
             let mut l: Vec<Line> = vec![
@@ -233,22 +328,48 @@ impl LaunchTab {
             match progress {
                 Ok(u) => {
                     u.units().iter()
-                        .map(|p| {
-                            let file_progress = p.read();
-                            match file_progress {
-                                Ok(progress) => l.push(Line::raw(format!("progress {:?}", progress.name()))),
-                                _ => {l.push(Line::raw("progress not available"))},
+                        .for_each(|p| l.push(Line::raw(format!(
+                            "progress {:?}{}",
+                            p.name(),
+                            if p.is_stalled() {
+                                " (stalled, retrying)".to_string()
+                            } else if p.retries() > 0 {
+                                format!(" (retry {})", p.retries())
+                            } else {
+                                String::new()
                             }
-                        })
-                        .collect()
+                        ))))
                 },
                 _ => { l.push(Line::raw("not downloading")) }
             }
             l
         } else {
-            vec![Line::raw("not downloading")]
+            let mut l = vec![Line::raw("not downloading")];
+            for install in &self.unfinished_installs {
+                let (done, planned) = install.files();
+                l.push(Line::raw(format!(
+                    "Unfinished install of {}: {}/{} files, R to resume",
+                    install.version(),
+                    done,
+                    planned
+                )));
+            }
+            l
         };
 
+        if let Ok(report) = self.download_report.try_lock() {
+            if let Some(report) = report.as_ref() {
+                lines.push(Line::raw(format!("{}", report)));
+                for failed in report.failed() {
+                    lines.push(Line::raw(format!(
+                        "{}: {}",
+                        failed.file().path(),
+                        failed.outcome()
+                    )));
+                }
+            }
+        }
+
         // Renderizar la lista
         lines.push(Line::raw("¡DOWNLOADING!"));
         Widget::render(List::new(lines).block(block), area, buf);
@@ -264,6 +385,15 @@ impl LaunchTab {
             Line::raw(format!("Profile: {}", "not implemented")),
             Line::raw(format!("User: {}", UserBuilder::default().username())),
             Line::raw(format!("State: {:?}", self.state)),
+            Line::raw(format!("Step: {}", match &self.install_progress {
+                Some(progress) => progress.to_string(),
+                None => "-".to_string(),
+            })),
+            Line::raw(format!("Bandwidth: {}", match BandwidthLimiter::global().rate() {
+                Some(rate) => format!("{}/s", format_bytes(rate)),
+                None => "unlimited".to_string(),
+            })),
+            Line::raw("A: download, R: resume, X: cancel download, -/+: bandwidth"),
         ];
 
         Paragraph::new(info_lines)
@@ -275,8 +405,29 @@ impl LaunchTab {
             .render(info, buf);
 
         // Renderiza progreso dentro del área `progress`
-        Paragraph::new("This is the launch tab")
-            .alignment(Alignment::Center)
+        let tracking = self.download_progress.as_ref().and_then(|p| p.try_lock().ok());
+        let (ratio, label) = match tracking.as_deref() {
+            Some(t) => {
+                let (done, total) = t.bytes();
+                let eta = match t.eta() {
+                    Some(eta) => format!("{} s left", eta.as_secs()),
+                    None => "-".to_string(),
+                };
+                let ratio = if total > 0 { (done as f64 / total as f64).min(1.0) } else { 0.0 };
+                let label = format!(
+                    "{} / {}, {}/s, {}",
+                    format_bytes(done),
+                    format_bytes(total),
+                    format_bytes(t.rate() as u64),
+                    eta
+                );
+                (ratio, label)
+            }
+            None => (0.0, "not downloading".to_string()),
+        };
+        Gauge::default()
+            .ratio(ratio)
+            .label(label)
             .block(Block::default()
                 .title("Progress")
                 .borders(Borders::ALL)
@@ -284,3 +435,14 @@ impl LaunchTab {
             .render(progress, buf);
     }
 }
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.0} {}", value, UNITS[unit])
+}